@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use crate::{read_columns, ColumnReadError, ColumnReport, Location, ReadMode, SkippedLine};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportOptions {
+    /// Number of equal-width histogram bins spanning both columns.
+    pub bins: usize,
+    /// Probabilities in `[0, 1]` to report quantiles for.
+    pub quantiles: Vec<f64>,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        ReportOptions { bins: 10, quantiles: vec![0.0, 0.25, 0.5, 0.75, 1.0] }
+    }
+}
+
+/// Counts per bin for both columns over the same edges, so the two can be
+/// compared bin by bin. Bin `i` covers `[edges[i], edges[i + 1])`, except the
+/// last one which also includes its upper edge.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub edges: Vec<f64>,
+    pub col1: Vec<usize>,
+    pub col2: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantile<T> {
+    pub probability: f64,
+    /// Nearest-rank quantile of each column; `None` when the column is empty.
+    pub col1: Option<T>,
+    pub col2: Option<T>,
+}
+
+#[derive(Debug)]
+pub struct DistributionReport<T = i32> {
+    pub histogram: Histogram,
+    pub quantiles: Vec<Quantile<T>>,
+    /// Largest gap between the two empirical CDFs.
+    pub ks_statistic: f64,
+    /// Distinct values found in one column but never in the other.
+    pub only_in_col1: Vec<T>,
+    pub only_in_col2: Vec<T>,
+    /// Multiset symmetric difference as `(value, col1 count - col2 count)`,
+    /// ascending by value: a positive surplus is that many extra copies in
+    /// col1, a negative one extra copies in col2.
+    pub symmetric_difference: Vec<(T, i64)>,
+    /// Lines the reader skipped, when the report was read in lenient mode.
+    pub skipped: Vec<SkippedLine>,
+}
+
+impl<T: Location> DistributionReport<T> {
+    pub fn new(mut col1: Vec<T>, mut col2: Vec<T>, options: &ReportOptions) -> Self {
+        col1.sort();
+        col2.sort();
+
+        let mut counts: BTreeMap<T, (i64, i64)> = BTreeMap::new();
+        col1.iter().for_each(|&v| counts.entry(v).or_insert((0, 0)).0 += 1);
+        col2.iter().for_each(|&v| counts.entry(v).or_insert((0, 0)).1 += 1);
+
+        DistributionReport {
+            histogram: histogram(&col1, &col2, options.bins),
+            quantiles: options.quantiles.iter().map(|&p| Quantile {
+                probability: p,
+                col1: nearest_rank(&col1, p),
+                col2: nearest_rank(&col2, p),
+            }).collect(),
+            ks_statistic: ks_statistic(&col1, &col2),
+            only_in_col1: counts.iter().filter(|(_, c)| c.1 == 0).map(|(&v, _)| v).collect(),
+            only_in_col2: counts.iter().filter(|(_, c)| c.0 == 0).map(|(&v, _)| v).collect(),
+            symmetric_difference: counts.iter()
+                .filter(|(_, c)| c.0 != c.1)
+                .map(|(&v, c)| (v, c.0 - c.1))
+                .collect(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+/// Reads two columns with `read_columns` and compares them.
+pub fn distribution_report<R: BufRead, T: Location>(
+    reader: R,
+    mode: ReadMode,
+    options: &ReportOptions,
+) -> Result<DistributionReport<T>, ColumnReadError> {
+    let ColumnReport { col1, col2, skipped } = read_columns(reader, mode)?;
+    let mut report = DistributionReport::new(col1, col2, options);
+    report.skipped = skipped;
+    Ok(report)
+}
+
+fn to_f64<T: Location>(value: T) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+fn histogram<T: Location>(col1: &[T], col2: &[T], bins: usize) -> Histogram {
+    let low = col1.first().into_iter().chain(col2.first()).min().copied();
+    let high = col1.last().into_iter().chain(col2.last()).max().copied();
+    let (Some(low), Some(high), true) = (low, high, bins > 0) else {
+        return Histogram { edges: Vec::new(), col1: Vec::new(), col2: Vec::new() };
+    };
+    let (low, high) = (to_f64(low), to_f64(high));
+    let width = (high - low) / bins as f64;
+    let edges = (0..=bins).map(|i| low + width * i as f64).collect();
+    let count = |column: &[T]| {
+        let mut counts = vec![0; bins];
+        column.iter().for_each(|&v| {
+            let bin = if width > 0.0 { ((to_f64(v) - low) / width) as usize } else { 0 };
+            counts[bin.min(bins - 1)] += 1;
+        });
+        counts
+    };
+    Histogram { edges, col1: count(col1), col2: count(col2) }
+}
+
+/// The smallest value with at least `p` of the (sorted) column at or below it.
+fn nearest_rank<T: Location>(sorted: &[T], p: f64) -> Option<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p.clamp(0.0, 1.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.saturating_sub(1)])
+}
+
+/// Walks both sorted columns together, evaluating the CDF gap after each
+/// distinct value.
+fn ks_statistic<T: Location>(col1: &[T], col2: &[T]) -> f64 {
+    if col1.is_empty() || col2.is_empty() {
+        return if col1.len() == col2.len() { 0.0 } else { 1.0 };
+    }
+    let (n1, n2) = (col1.len() as f64, col2.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut statistic: f64 = 0.0;
+    while i < col1.len() && j < col2.len() {
+        let value = col1[i].min(col2[j]);
+        while i < col1.len() && col1[i] == value {
+            i += 1;
+        }
+        while j < col2.len() && col2[j] == value {
+            j += 1;
+        }
+        statistic = statistic.max((i as f64 / n1 - j as f64 / n2).abs());
+    }
+    statistic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    #[test]
+    fn report_on_example() {
+        let file = File::open("data/example_1.txt").unwrap();
+        let options = ReportOptions { bins: 4, quantiles: vec![0.5, 1.0] };
+        let report = distribution_report::<_, i32>(BufReader::new(file), ReadMode::Strict, &options).unwrap();
+
+        assert_eq!(report.histogram.edges, vec![1.0, 3.0, 5.0, 7.0, 9.0]);
+        assert_eq!(report.histogram.col1, vec![2, 4, 0, 0]);
+        assert_eq!(report.histogram.col2, vec![0, 4, 1, 1]);
+        assert_eq!(report.quantiles[0], Quantile { probability: 0.5, col1: Some(3), col2: Some(3) });
+        assert_eq!(report.quantiles[1], Quantile { probability: 1.0, col1: Some(4), col2: Some(9) });
+        assert!((report.ks_statistic - 2.0 / 6.0).abs() < 1e-12);
+        assert_eq!(report.only_in_col1, vec![1, 2]);
+        assert_eq!(report.only_in_col2, vec![5, 9]);
+        assert_eq!(report.symmetric_difference, vec![(1, 1), (2, 1), (5, -1), (9, -1)]);
+    }
+
+    #[test]
+    fn empty_columns() {
+        let report = DistributionReport::<u64>::new(Vec::new(), vec![7], &ReportOptions::default());
+        assert_eq!(report.quantiles[0].col1, None);
+        assert_eq!(report.quantiles[0].col2, Some(7));
+        assert_eq!(report.histogram.col2, vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(report.ks_statistic, 1.0);
+        assert_eq!(report.symmetric_difference, vec![(7, -1)]);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::Location;
+
+/// How one distinct left-column value feeds into the similarity score.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Contribution<T> {
+    pub value: T,
+    /// How many times `value` appears in the left column.
+    pub left_count: i64,
+    /// How many times `value` appears in the right column.
+    pub frequency: i64,
+    /// `value * frequency * left_count`, this value's share of the score.
+    pub contribution: i128,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Value,
+    Frequency,
+    Contribution,
+}
+
+/// The similarity score broken down per distinct left-column value,
+/// initially in ascending order of value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimilarityExplanation<T> {
+    pub contributions: Vec<Contribution<T>>,
+}
+
+/// Works on any location type that fits in an `i128`, which is wide enough
+/// for every contribution and the total.
+pub fn explain_similarity<T: Location + Into<i128>>(col1: &[T], col2: &[T]) -> SimilarityExplanation<T> {
+    let mut frequency_map: HashMap<T, i64> = HashMap::new();
+    col2.iter().for_each(|&key| {
+        *frequency_map.entry(key).or_insert(0) += 1;
+    });
+    let mut left_counts: BTreeMap<T, i64> = BTreeMap::new();
+    col1.iter().for_each(|&key| {
+        *left_counts.entry(key).or_insert(0) += 1;
+    });
+    let contributions = left_counts.into_iter().map(|(value, left_count)| {
+        let frequency = *frequency_map.get(&value).unwrap_or(&0);
+        Contribution { value, left_count, frequency, contribution: value.into() * frequency as i128 * left_count as i128 }
+    }).collect();
+    SimilarityExplanation { contributions }
+}
+
+impl<T: Location + fmt::Display> SimilarityExplanation<T> {
+    /// The score itself; equal to `get_similarity_measure` on the same columns.
+    pub fn total(&self) -> i128 {
+        self.contributions.iter().map(|c| c.contribution).sum()
+    }
+
+    pub fn sort_by(&mut self, key: SortKey, descending: bool) {
+        self.contributions.sort_by(|a, b| {
+            let ordering = match key {
+                SortKey::Value => a.value.cmp(&b.value),
+                SortKey::Frequency => a.frequency.cmp(&b.frequency).then(a.value.cmp(&b.value)),
+                SortKey::Contribution => a.contribution.cmp(&b.contribution).then(a.value.cmp(&b.value)),
+            };
+            if descending { ordering.reverse() } else { ordering }
+        });
+    }
+
+    /// The `k` values whose contributions weigh the most on the score, largest
+    /// magnitude first. Values that contribute nothing are never returned.
+    pub fn top_k(&self, k: usize) -> Vec<&Contribution<T>> {
+        let mut ranked: Vec<&Contribution<T>> = self.contributions.iter().filter(|c| c.contribution != 0).collect();
+        ranked.sort_by(|a, b| b.contribution.abs().cmp(&a.contribution.abs()).then(a.value.cmp(&b.value)));
+        ranked.truncate(k);
+        ranked
+    }
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "value,left_count,frequency,contribution")?;
+        for c in &self.contributions {
+            writeln!(writer, "{},{},{},{}", c.value, c.left_count, c.frequency, c.contribution)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `{"total": .., "contributions": [{..}, ..]}`.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "{{\"total\":{},\"contributions\":[", self.total())?;
+        for (i, c) in self.contributions.iter().enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+            write!(
+                writer,
+                "{{\"value\":{},\"left_count\":{},\"frequency\":{},\"contribution\":{}}}",
+                c.value, c.left_count, c.frequency, c.contribution
+            )?;
+        }
+        writeln!(writer, "]}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_similarity_measure, read_file_and_store_columns};
+
+    #[test]
+    fn explains_example() {
+        let (col1, col2) = read_file_and_store_columns("data/example_1.txt").unwrap();
+        let mut explanation = explain_similarity(&col1, &col2);
+        assert_eq!(explanation.total(), get_similarity_measure::<i32, i128>(col1, col2).unwrap());
+        assert_eq!(
+            explanation.top_k(1)[0],
+            &Contribution { value: 3, left_count: 3, frequency: 3, contribution: 27 }
+        );
+
+        explanation.sort_by(SortKey::Contribution, true);
+        let mut csv = Vec::new();
+        explanation.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "value,left_count,frequency,contribution\n3,3,3,27\n4,1,1,4\n2,1,0,0\n1,1,0,0\n"
+        );
+
+        let mut json = Vec::new();
+        explanation.write_json(&mut json).unwrap();
+        assert!(String::from_utf8(json).unwrap().starts_with(
+            "{\"total\":31,\"contributions\":[{\"value\":3,\"left_count\":3,\"frequency\":3,\"contribution\":27},"
+        ));
+
+        let wide = explain_similarity(&[u64::MAX; 2], &[u64::MAX; 3]);
+        assert_eq!(wide.total(), 6 * u64::MAX as i128);
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::BTreeMap;
+
+use crate::Location;
+
+/// Smallest target number of distinct values per block of the distance index.
+const BLOCK: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Left,
+    Right,
+}
+
+/// The two location lists as multisets, with the similarity score and the
+/// sorted distance kept current as values are inserted and removed.
+///
+/// The similarity score is updated in O(log n). The sorted distance uses
+/// the identity `Σ|a_i - b_i| = Σ_k (k' - k) · |D(k)|`, where `k, k'` are
+/// consecutive distinct values across both lists and `D(k)` counts left values
+/// `<= k` minus right values `<= k`. An update adds ±1 to `D` for every value
+/// above it, so the `n` distinct values are cut into blocks of about `√n`
+/// that can each re-price a shift of all their `D`s in O(log n). Blocks are
+/// split when they grow past twice that size and merged with a neighbour
+/// when they shrink below half of it, so there are always O(√n) of them and
+/// an update costs O(√n log n) rather than the O(n log n) of re-sorting.
+/// A logarithmic bound would need a tree that can add ±1 to a range of `D`
+/// and still report the weighted sum of `|D|` over it. That means knowing
+/// how much weight sits at each `D` below zero after the shift, which
+/// segment-tree lazy tags cannot summarise, so the blocks stay instead.
+///
+/// Any location type that fits in an `i128` can be indexed; both scores are
+/// kept in `i128`, which none of them can overflow.
+pub struct LocationLists<T = i32> {
+    left: BTreeMap<T, i64>,
+    right: BTreeMap<T, i64>,
+    left_len: usize,
+    right_len: usize,
+    similarity: i128,
+    distance: i128,
+    blocks: Vec<Block<T>>,
+}
+
+struct Block<T> {
+    keys: Vec<T>,
+    /// Left count minus right count for each key.
+    net: Vec<i64>,
+    /// `D` just before the first key, i.e. the summed `net` of earlier blocks.
+    base: i64,
+    /// Running `net` sums within the block, in ascending order, along with
+    /// prefix sums of their weights and of weight times running sum.
+    sorted_prefixes: Vec<i64>,
+    weight_sums: Vec<i128>,
+    weighted_prefix_sums: Vec<i128>,
+}
+
+impl<T> Default for Block<T> {
+    fn default() -> Self {
+        Block {
+            keys: Vec::new(),
+            net: Vec::new(),
+            base: 0,
+            sorted_prefixes: Vec::new(),
+            weight_sums: Vec::new(),
+            weighted_prefix_sums: Vec::new(),
+        }
+    }
+}
+
+impl<T: Location + Into<i128>> Block<T> {
+    fn rebuild(&mut self, next_first: Option<T>) {
+        let n = self.keys.len();
+        let mut pairs: Vec<(i64, i128)> = Vec::with_capacity(n);
+        let mut prefix = 0;
+        for i in 0..n {
+            prefix += self.net[i];
+            let next = if i + 1 < n { Some(self.keys[i + 1]) } else { next_first };
+            let weight = next.map_or(0, |k| k.into() - self.keys[i].into());
+            pairs.push((prefix, weight));
+        }
+        pairs.sort_unstable();
+        self.sorted_prefixes = pairs.iter().map(|&(p, _)| p).collect();
+        self.weight_sums = std::iter::once(0).chain(pairs.iter().scan(0, |acc, &(_, w)| {
+            *acc += w;
+            Some(*acc)
+        })).collect();
+        self.weighted_prefix_sums = std::iter::once(0).chain(pairs.iter().scan(0, |acc, &(p, w)| {
+            *acc += p as i128 * w;
+            Some(*acc)
+        })).collect();
+    }
+
+    /// `Σ weight · |base + prefix|` over the block.
+    fn contribution(&self) -> i128 {
+        let n = self.sorted_prefixes.len();
+        if n == 0 {
+            return 0;
+        }
+        let split = self.sorted_prefixes.partition_point(|&p| p < -self.base);
+        let (w_neg, wp_neg) = (self.weight_sums[split], self.weighted_prefix_sums[split]);
+        let (w_all, wp_all) = (self.weight_sums[n], self.weighted_prefix_sums[n]);
+        self.base as i128 * (w_all - 2 * w_neg) + wp_all - 2 * wp_neg
+    }
+}
+
+impl<T> Default for LocationLists<T> {
+    fn default() -> Self {
+        LocationLists {
+            left: BTreeMap::new(),
+            right: BTreeMap::new(),
+            left_len: 0,
+            right_len: 0,
+            similarity: 0,
+            distance: 0,
+            blocks: Vec::new(),
+        }
+    }
+}
+
+impl<T: Location + Into<i128>> LocationLists<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_columns(col1: &[T], col2: &[T]) -> Self {
+        let mut lists = Self::new();
+        col1.iter().for_each(|&v| *lists.left.entry(v).or_insert(0) += 1);
+        col2.iter().for_each(|&v| *lists.right.entry(v).or_insert(0) += 1);
+        lists.left_len = col1.len();
+        lists.right_len = col2.len();
+        lists.similarity = lists.left.iter()
+            .map(|(&v, &count)| v.into() * count as i128 * lists.count(Column::Right, v) as i128)
+            .sum();
+
+        let mut net: BTreeMap<T, i64> = lists.left.clone();
+        lists.right.iter().for_each(|(&v, &count)| *net.entry(v).or_insert(0) -= count);
+        let net: Vec<(T, i64)> = net.into_iter().collect();
+        let mut base = 0;
+        lists.blocks = net.chunks(lists.block_size()).map(|chunk| {
+            let block = Block {
+                keys: chunk.iter().map(|&(k, _)| k).collect(),
+                net: chunk.iter().map(|&(_, n)| n).collect(),
+                base,
+                ..Block::default()
+            };
+            base += block.net.iter().sum::<i64>();
+            block
+        }).collect();
+        lists.rebuild_blocks(0..lists.blocks.len());
+        lists
+    }
+
+    pub fn len(&self, column: Column) -> usize {
+        match column {
+            Column::Left => self.left_len,
+            Column::Right => self.right_len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left_len == 0 && self.right_len == 0
+    }
+
+    pub fn count(&self, column: Column, value: T) -> i64 {
+        let counts = match column {
+            Column::Left => &self.left,
+            Column::Right => &self.right,
+        };
+        *counts.get(&value).unwrap_or(&0)
+    }
+
+    /// Same as `get_similarity_measure` over the current lists.
+    pub fn similarity(&self) -> i128 {
+        self.similarity
+    }
+
+    /// Same as `sum_all_diffs` over the current lists. Pairing the i-th
+    /// smallest values only makes sense when both lists are equally long,
+    /// so this is `None` while they are not.
+    pub fn sorted_distance(&self) -> Option<i128> {
+        (self.left_len == self.right_len).then_some(self.distance)
+    }
+
+    pub fn insert(&mut self, column: Column, value: T) {
+        let other = match column {
+            Column::Left => Column::Right,
+            Column::Right => Column::Left,
+        };
+        self.similarity += value.into() * self.count(other, value) as i128;
+        let (counts, len, delta) = match column {
+            Column::Left => (&mut self.left, &mut self.left_len, 1),
+            Column::Right => (&mut self.right, &mut self.right_len, -1),
+        };
+        *counts.entry(value).or_insert(0) += 1;
+        *len += 1;
+        self.shift(value, delta, true);
+    }
+
+    /// Removes one occurrence of `value` from `column`, returning whether
+    /// there was one to remove.
+    pub fn remove(&mut self, column: Column, value: T) -> bool {
+        let (counts, len, delta) = match column {
+            Column::Left => (&mut self.left, &mut self.left_len, -1),
+            Column::Right => (&mut self.right, &mut self.right_len, 1),
+        };
+        match counts.get_mut(&value) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                counts.remove(&value);
+            }
+            None => return false,
+        }
+        *len -= 1;
+        let other = match column {
+            Column::Left => Column::Right,
+            Column::Right => Column::Left,
+        };
+        self.similarity -= value.into() * self.count(other, value) as i128;
+        let still_present = self.left.contains_key(&value) || self.right.contains_key(&value);
+        self.shift(value, delta, still_present);
+        true
+    }
+
+    /// Adds `delta` to the net count at `value`, which shifts `D` by `delta`
+    /// for `value` and everything above it.
+    fn shift(&mut self, value: T, delta: i64, still_present: bool) {
+        if self.blocks.is_empty() {
+            self.blocks.push(Block::default());
+        }
+        let size = self.block_size();
+        let b = self.blocks
+            .partition_point(|block| block.keys.first().is_some_and(|&k| k <= value))
+            .saturating_sub(1);
+        let first = b.saturating_sub(1);
+
+        // The block holding `value` and the one before it (whose last weight
+        // reaches into it) are rebuilt; later blocks only move their base.
+        for c in first..=b {
+            self.distance -= self.blocks[c].contribution();
+        }
+        for block in &mut self.blocks[b + 1..] {
+            self.distance -= block.contribution();
+            block.base += delta;
+            self.distance += block.contribution();
+        }
+
+        let block = &mut self.blocks[b];
+        match block.keys.binary_search(&value) {
+            Ok(i) if still_present => block.net[i] += delta,
+            Ok(i) => {
+                block.keys.remove(i);
+                block.net.remove(i);
+            }
+            Err(i) => {
+                block.keys.insert(i, value);
+                block.net.insert(i, delta);
+            }
+        }
+
+        let end = if block.keys.is_empty() {
+            self.blocks.remove(b);
+            b
+        } else if block.keys.len() > 2 * size {
+            self.split_block(b, size);
+            b + 2
+        } else if block.keys.len() < size / 2 && self.blocks.len() > 1 {
+            // Underfull blocks are merged into a neighbour, so removals shrink
+            // the block count as insertions grow it. The block before `b`
+            // has already been taken out of `distance`; the one after has not.
+            let into = if b + 1 < self.blocks.len() {
+                self.distance -= self.blocks[b + 1].contribution();
+                b
+            } else {
+                b - 1
+            };
+            let next = self.blocks.remove(into + 1);
+            let block = &mut self.blocks[into];
+            block.keys.extend(next.keys);
+            block.net.extend(next.net);
+            let len = block.keys.len();
+            if len > 2 * size {
+                self.split_block(into, len / 2);
+                into + 2
+            } else {
+                into + 1
+            }
+        } else {
+            b + 1
+        };
+        self.rebuild_blocks(first..end);
+    }
+
+    /// Moves the keys of block `b` from `at` onwards into a new block after it.
+    fn split_block(&mut self, b: usize, at: usize) {
+        let block = &mut self.blocks[b];
+        let keys = block.keys.split_off(at);
+        let net = block.net.split_off(at);
+        let base = block.base + block.net.iter().sum::<i64>();
+        self.blocks.insert(b + 1, Block { keys, net, base, ..Block::default() });
+    }
+
+    /// Blocks grow with the number of distinct values, so that the number of
+    /// blocks stays close to the number of values in each.
+    fn block_size(&self) -> usize {
+        BLOCK.max((self.left.len() + self.right.len()).isqrt())
+    }
+
+    fn rebuild_blocks(&mut self, range: std::ops::Range<usize>) {
+        for c in range {
+            let next_first = self.blocks.get(c + 1).map(|block| block.keys[0]);
+            self.blocks[c].rebuild(next_first);
+            self.distance += self.blocks[c].contribution();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{get_similarity_measure, read_file_and_store_columns, sum_all_diffs};
+
+    #[test]
+    fn from_columns_matches_batch_scores() {
+        let (col1, col2) = read_file_and_store_columns("data/input_1.txt").unwrap();
+        let lists = LocationLists::from_columns(&col1, &col2);
+        assert_eq!(lists.sorted_distance(), Some(sum_all_diffs::<i32, i128>(col1.clone(), col2.clone()).unwrap()));
+        assert_eq!(lists.similarity(), get_similarity_measure::<i32, i128>(col1, col2).unwrap());
+    }
+
+    #[test]
+    fn updates_track_batch_scores() {
+        let mut lists = LocationLists::new();
+        let (mut col1, mut col2) = (Vec::new(), Vec::new());
+        let mut rng = StdRng::seed_from_u64(7);
+        for step in 0..3000 {
+            let column = if rng.gen_bool(0.5) { Column::Left } else { Column::Right };
+            let values = if column == Column::Left { &mut col1 } else { &mut col2 };
+            if step > 1000 && rng.gen_range(0..3) == 0 && !values.is_empty() {
+                let value = values.swap_remove(rng.gen_range(0..values.len()));
+                assert!(lists.remove(column, value));
+            } else {
+                let value = rng.gen_range(-100..400);
+                values.push(value);
+                lists.insert(column, value);
+            }
+            assert_eq!(lists.similarity(), get_similarity_measure::<i32, i128>(col1.clone(), col2.clone()).unwrap());
+            if col1.len() == col2.len() {
+                assert_eq!(lists.sorted_distance(), Some(sum_all_diffs::<i32, i128>(col1.clone(), col2.clone()).unwrap()));
+            } else {
+                assert_eq!(lists.sorted_distance(), None);
+            }
+        }
+        assert!(!lists.remove(Column::Left, 10_000));
+    }
+
+    #[test]
+    fn removals_merge_underfull_blocks() {
+        let values: Vec<i32> = (0..20_000).collect();
+        let mut lists = LocationLists::from_columns(&values, &values);
+        // Thinning every block out evenly leaves none of them empty.
+        for &value in values.iter().filter(|&&v| v % 20 != 0) {
+            assert!(lists.remove(Column::Left, value));
+            assert!(lists.remove(Column::Right, value));
+        }
+        assert!(lists.blocks.len() <= 2 * 1_000 / BLOCK + 1, "{} blocks", lists.blocks.len());
+        assert_eq!(lists.sorted_distance(), Some(0));
+        let remaining: Vec<i32> = values.into_iter().filter(|v| v % 20 == 0).collect();
+        assert_eq!(lists.similarity(), get_similarity_measure::<i32, i128>(remaining.clone(), remaining).unwrap());
+    }
+
+    #[test]
+    fn wide_values_do_not_overflow() {
+        let mut lists = LocationLists::from_columns(&[i64::MIN, i64::MAX], &[i64::MAX, i64::MIN]);
+        assert_eq!((lists.sorted_distance(), lists.similarity()), (Some(0), -1));
+        assert!(lists.remove(Column::Left, i64::MAX));
+        assert!(lists.remove(Column::Right, i64::MIN));
+        assert_eq!(lists.sorted_distance(), Some(u64::MAX as i128));
+        lists.insert(Column::Left, i64::MAX);
+        assert_eq!(lists.similarity(), i64::MAX as i128);
+    }
+}
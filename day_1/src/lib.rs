@@ -1,57 +1,310 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
+use std::hash::Hash;
+use std::marker::PhantomData;
 use std::io::{self, BufRead};
-use std::collections::HashMap;
+use std::str::FromStr;
+
+use num_traits::{NumCast, PrimInt};
+
+pub mod distribution;
+pub mod explain;
+pub mod index;
+pub mod metrics;
+
+/// Why a line of the two-column input could not be turned into a pair of values.
+/// Line and column numbers are 1-based so they can be matched against an editor.
+#[derive(Debug)]
+pub enum ColumnReadError {
+    /// The input file could not be opened, so no line was read.
+    Open { path: String, source: io::Error },
+    Io { line: usize, source: io::Error },
+    /// The line was read but is not valid UTF-8.
+    InvalidUtf8 { line: usize },
+    InvalidToken { line: usize, column: usize, token: String },
+    WrongColumnCount { line: usize, expected: usize, found: usize },
+}
 
-fn read_file_and_store_columns(file_path: &str) -> io::Result<(Vec<i32>, Vec<i32>)> {
-    // Open the file
-    let file = File::open(file_path)?;
-    let reader = io::BufReader::new(file);
-
-    // Create two vectors to store the columns
-    let mut col1: Vec<i32> = Vec::new();
-    let mut col2: Vec<i32> = Vec::new();
-
-    reader.lines().for_each(|line| {
-        let line: String = line.expect("");
-        let numbers: Vec<i32> = line
-            .split_whitespace()
-            .filter_map(|s| s.parse::<i32>().ok())
-            .collect();
-        if numbers.len() == 2 {
-            col1.push(numbers[0]);
-            col2.push(numbers[1]);
+impl ColumnReadError {
+    /// The line the error is on; `None` if the input could not be opened.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ColumnReadError::Open { .. } => None,
+            ColumnReadError::Io { line, .. }
+            | ColumnReadError::InvalidUtf8 { line }
+            | ColumnReadError::InvalidToken { line, .. }
+            | ColumnReadError::WrongColumnCount { line, .. } => Some(*line),
         }
-    });
+    }
+}
+
+impl fmt::Display for ColumnReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnReadError::Open { path, source } => {
+                write!(f, "cannot open {}: {}", path, source)
+            }
+            ColumnReadError::Io { line, source } => {
+                write!(f, "line {}: read failed: {}", line, source)
+            }
+            ColumnReadError::InvalidUtf8 { line } => {
+                write!(f, "line {}: not valid UTF-8", line)
+            }
+            ColumnReadError::InvalidToken { line, column, token } => {
+                write!(f, "line {}, column {}: invalid token {:?}", line, column, token)
+            }
+            ColumnReadError::WrongColumnCount { line, expected, found } => {
+                write!(f, "line {}: expected {} columns, found {}", line, expected, found)
+            }
+        }
+    }
+}
+
+impl Error for ColumnReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ColumnReadError::Open { source, .. } | ColumnReadError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with a line that does not parse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Stop at the first bad line and return its error.
+    Strict,
+    /// Keep going and record every bad line in `ColumnReport::skipped`.
+    Lenient,
+}
+
+#[derive(Debug)]
+pub struct SkippedLine {
+    pub content: String,
+    pub error: ColumnReadError,
+}
+
+/// Integer types a location list can hold, e.g. `i32`, `i64`, `u64` or `i128`.
+pub trait Location: PrimInt + Hash + FromStr {}
+
+impl<T: PrimInt + Hash + FromStr> Location for T {}
+
+#[derive(Debug)]
+pub struct ColumnReport<T = i32> {
+    pub col1: Vec<T>,
+    pub col2: Vec<T>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+/// Same as `ColumnReport`, for inputs with any number of columns.
+#[derive(Debug)]
+pub struct MultiColumnReport<T = i32> {
+    pub columns: Vec<Vec<T>>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+/// Streams rows of exactly `width` values out of any `BufRead`, one line at a time.
+/// Blank lines are not data and are passed over; every other line yields
+/// either a row or the error describing what is wrong with it.
+pub struct ColumnRows<R, T = i32> {
+    reader: R,
+    buffer: Vec<u8>,
+    line: usize,
+    width: usize,
+    values: PhantomData<T>,
+}
+
+impl<R: BufRead, T: FromStr> ColumnRows<R, T> {
+    pub fn new(reader: R) -> Self {
+        Self::with_width(reader, 2)
+    }
+
+    pub fn with_width(reader: R, width: usize) -> Self {
+        ColumnRows { reader, buffer: Vec::new(), line: 0, width, values: PhantomData }
+    }
+
+    /// The raw text of the line most recently returned by `next`, with
+    /// undecodable bytes replaced by U+FFFD.
+    pub fn current_line(&self) -> Cow<'_, str> {
+        let line = self.buffer.strip_suffix(b"\n").unwrap_or(&self.buffer);
+        String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+}
+
+fn parse_row<T: FromStr>(line: &str, line_no: usize, width: usize) -> Result<Vec<T>, ColumnReadError> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() != width {
+        return Err(ColumnReadError::WrongColumnCount { line: line_no, expected: width, found: tokens.len() });
+    }
+    tokens.iter().enumerate().map(|(column, token)| {
+        token.parse::<T>().map_err(|_| ColumnReadError::InvalidToken {
+            line: line_no,
+            column: column + 1,
+            token: token.to_string(),
+        })
+    }).collect()
+}
+
+impl<R: BufRead, T: FromStr> Iterator for ColumnRows<R, T> {
+    type Item = Result<Vec<T>, ColumnReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            self.line += 1;
+            // Reading raw bytes consumes a whole line even when it is not
+            // UTF-8, so a bad line can be skipped like any other.
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => match std::str::from_utf8(&self.buffer) {
+                    Ok(text) if text.trim().is_empty() => continue,
+                    Ok(text) => return Some(parse_row(text, self.line, self.width)),
+                    Err(_) => return Some(Err(ColumnReadError::InvalidUtf8 { line: self.line })),
+                },
+                Err(source) => return Some(Err(ColumnReadError::Io { line: self.line, source })),
+            }
+        }
+    }
+}
+
+pub fn read_n_columns<R: BufRead, T: FromStr>(reader: R, width: usize, mode: ReadMode) -> Result<MultiColumnReport<T>, ColumnReadError> {
+    let mut rows = ColumnRows::with_width(reader, width);
+    let mut report = MultiColumnReport { columns: (0..width).map(|_| Vec::new()).collect(), skipped: Vec::new() };
+    while let Some(row) = rows.next() {
+        match row {
+            Ok(values) => {
+                report.columns.iter_mut().zip(values).for_each(|(column, value)| column.push(value));
+            }
+            // An I/O error leaves the stream in an unknown state, so it is fatal either way.
+            Err(error @ ColumnReadError::Io { .. }) => return Err(error),
+            Err(error) if mode == ReadMode::Lenient => {
+                report.skipped.push(SkippedLine { content: rows.current_line().to_string(), error });
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(report)
+}
+
+pub fn read_columns<R: BufRead, T: FromStr>(reader: R, mode: ReadMode) -> Result<ColumnReport<T>, ColumnReadError> {
+    let MultiColumnReport { mut columns, skipped } = read_n_columns(reader, 2, mode)?;
+    let col2 = columns.pop().unwrap_or_default();
+    let col1 = columns.pop().unwrap_or_default();
+    Ok(ColumnReport { col1, col2, skipped })
+}
+
+pub fn read_file_and_store_columns<T: FromStr>(file_path: &str) -> Result<(Vec<T>, Vec<T>), ColumnReadError> {
+    let file = File::open(file_path).map_err(|source| ColumnReadError::Open { path: file_path.to_string(), source })?;
+    let report = read_columns(io::BufReader::new(file), ReadMode::Strict)?;
+    Ok((report.col1, report.col2))
+}
+
+/// A sum or product left the range of the accumulator type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OverflowError {
+    pub operation: &'static str,
+}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "overflow while computing the {}", self.operation)
+    }
+}
+
+impl Error for OverflowError {}
+
+/// Why a score could not be computed from a file.
+#[derive(Debug)]
+pub enum ScoreError {
+    Read(ColumnReadError),
+    Overflow(OverflowError),
+}
+
+impl fmt::Display for ScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScoreError::Read(error) => error.fmt(f),
+            ScoreError::Overflow(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for ScoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScoreError::Read(error) => Some(error),
+            ScoreError::Overflow(error) => Some(error),
+        }
+    }
+}
 
-    Ok((col1, col2)) // Return the two vectors
+impl From<ColumnReadError> for ScoreError {
+    fn from(error: ColumnReadError) -> Self {
+        ScoreError::Read(error)
+    }
 }
 
-fn sum_all_diffs(mut col1: Vec<i32>, mut col2: Vec<i32>) -> i32 {
+impl From<OverflowError> for ScoreError {
+    fn from(error: OverflowError) -> Self {
+        ScoreError::Overflow(error)
+    }
+}
+
+/// Sorted L1 distance between the columns, accumulated in `A`. With `A = T`
+/// every step is checked; a wider `A` (e.g. `i64` for `i32` columns) keeps
+/// large inputs exact.
+pub fn sum_all_diffs<T: Location, A: PrimInt + From<T>>(mut col1: Vec<T>, mut col2: Vec<T>) -> Result<A, OverflowError> {
+    let overflow = OverflowError { operation: "sorted distance" };
     col1.sort();
     col2.sort();
-    col1.iter().zip(col2.iter())
-    .map(|(a, b)| (a - b).abs())
-    .sum()
+    col1.into_iter().zip(col2)
+    .try_fold(A::zero(), |sum, (a, b)| {
+        let (a, b) = (<A as From<T>>::from(a), <A as From<T>>::from(b));
+        let diff = if a > b { a.checked_sub(&b) } else { b.checked_sub(&a) };
+        diff.and_then(|diff| sum.checked_add(&diff)).ok_or(overflow)
+    })
+}
+
+pub fn sum_of_diffs_from_file(file_path: &str) -> i32{
+    try_sum_of_diffs_from_file::<i32, i32>(file_path).expect("")
 }
 
-fn sum_of_diffs_from_file(file_path: &str) -> i32{
-    let (col1, col2) = read_file_and_store_columns(file_path).expect("");
-    sum_all_diffs(col1, col2)
+/// `sum_of_diffs_from_file` for columns of `T`, accumulated in `A`, reporting
+/// unreadable input and overflow instead of panicking.
+pub fn try_sum_of_diffs_from_file<T: Location, A: PrimInt + From<T>>(file_path: &str) -> Result<A, ScoreError> {
+    let (col1, col2) = read_file_and_store_columns::<T>(file_path)?;
+    Ok(sum_all_diffs(col1, col2)?)
 }
 
-fn get_similarity_measure(col1: Vec<i32>, col2: Vec<i32>) -> i32 {
-    let mut frequency_map:  HashMap<i32, i32> = HashMap::new();
+/// Similarity score of the columns, accumulated in `A` with the same
+/// checked or widening behaviour as `sum_all_diffs`.
+pub fn get_similarity_measure<T: Location, A: PrimInt + From<T>>(col1: Vec<T>, col2: Vec<T>) -> Result<A, OverflowError> {
+    let overflow = OverflowError { operation: "similarity score" };
+    let mut frequency_map:  HashMap<T, usize> = HashMap::new();
     col2.iter().for_each(|&key| {
         *frequency_map.entry(key).or_insert(0) += 1;
     });
     col1.iter()
-    .map(|&num| num * frequency_map.get(&num).unwrap_or(&0))
-    .sum()
+    .try_fold(A::zero(), |sum, &num| {
+        let frequency: A = NumCast::from(*frequency_map.get(&num).unwrap_or(&0)).ok_or(overflow)?;
+        <A as From<T>>::from(num).checked_mul(&frequency)
+            .and_then(|product| sum.checked_add(&product))
+            .ok_or(overflow)
+    })
 }
 
-fn get_similarity_measure_from_file(file_path: &str) -> i32 {
-    let (col1, col2) = read_file_and_store_columns(file_path).expect("");
-    get_similarity_measure(col1, col2)
+pub fn get_similarity_measure_from_file(file_path: &str) -> i32 {
+    try_get_similarity_measure_from_file::<i32, i32>(file_path).expect("")
+}
+
+/// `get_similarity_measure_from_file` for columns of `T`, accumulated in `A`,
+/// reporting unreadable input and overflow instead of panicking.
+pub fn try_get_similarity_measure_from_file<T: Location, A: PrimInt + From<T>>(file_path: &str) -> Result<A, ScoreError> {
+    let (col1, col2) = read_file_and_store_columns::<T>(file_path)?;
+    Ok(get_similarity_measure(col1, col2)?)
 }
 
 #[cfg(test)]
@@ -64,5 +317,72 @@ mod tests {
         assert_eq!(sum_of_diffs_from_file("data/input_1.txt"), 2113135);
         assert_eq!(get_similarity_measure_from_file("data/example_1.txt"), 31);
         assert_eq!(get_similarity_measure_from_file("data/input_1.txt"), 19097157);
+        assert_eq!(try_sum_of_diffs_from_file::<i32, i64>("data/input_1.txt").unwrap(), 2113135);
+        assert_eq!(try_get_similarity_measure_from_file::<i32, i64>("data/input_1.txt").unwrap(), 19097157);
+    }
+
+    #[test]
+    fn strict_reader_names_the_bad_token() {
+        let input = "3   4\n4   x3\n";
+        let error = read_columns::<_, i32>(input.as_bytes(), ReadMode::Strict).unwrap_err();
+        match error {
+            ColumnReadError::InvalidToken { line, column, token } => {
+                assert_eq!((line, column, token.as_str()), (2, 2, "x3"));
+            }
+            other => panic!("unexpected error {}", other),
+        }
+    }
+
+    #[test]
+    fn lenient_reader_reports_skipped_lines() {
+        let input = "3   4\n\n1 2 3\n4   3\n";
+        let report = read_columns(input.as_bytes(), ReadMode::Lenient).unwrap();
+        assert_eq!((report.col1, report.col2), (vec![3, 4], vec![4, 3]));
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].content, "1 2 3");
+        assert_eq!(report.skipped[0].error.line(), Some(3));
+    }
+
+    #[test]
+    fn missing_file_has_no_line() {
+        let error = read_file_and_store_columns::<i32>("data/missing.txt").unwrap_err();
+        assert!(matches!(error, ColumnReadError::Open { .. }));
+        assert_eq!(error.line(), None);
+        assert!(matches!(try_sum_of_diffs_from_file::<i32, i64>("data/missing.txt"), Err(ScoreError::Read(_))));
+    }
+
+    #[test]
+    fn lenient_reader_skips_lines_that_are_not_utf8() {
+        let report = read_columns::<_, i32>(&b"3 4\n\xff\xfe 1\n4 3\n"[..], ReadMode::Lenient).unwrap();
+        assert_eq!((report.col1, report.col2), (vec![3, 4], vec![4, 3]));
+        assert_eq!(report.skipped.len(), 1);
+        assert!(matches!(report.skipped[0].error, ColumnReadError::InvalidUtf8 { line: 2 }));
+        assert_eq!(report.skipped[0].content, "\u{fffd}\u{fffd} 1");
+        let error = read_columns::<_, i32>(&b"3 4\n\xff\xfe 1\n"[..], ReadMode::Strict).unwrap_err();
+        assert_eq!(error.line(), Some(2));
+    }
+
+    #[test]
+    fn wider_types_and_overflow() {
+        let col1: Vec<i32> = vec![i32::MIN, i32::MAX];
+        let col2: Vec<i32> = vec![i32::MAX, i32::MIN];
+        assert_eq!(sum_all_diffs::<i32, i32>(col1.clone(), col2.clone()), Ok(0));
+        assert_eq!(sum_all_diffs::<i32, i32>(vec![i32::MIN], vec![i32::MAX]),
+                   Err(OverflowError { operation: "sorted distance" }));
+        assert_eq!(sum_all_diffs::<i32, i64>(vec![i32::MIN], vec![i32::MAX]), Ok(u32::MAX as i64));
+        assert_eq!(get_similarity_measure::<i32, i32>(vec![i32::MAX; 2], col1.clone()),
+                   Err(OverflowError { operation: "similarity score" }));
+        assert_eq!(get_similarity_measure::<i32, i64>(vec![i32::MAX; 2], col1), Ok(2 * i32::MAX as i64));
+
+        let input = "18446744073709551615 1\n0 18446744073709551615\n";
+        let report = read_columns::<_, u64>(input.as_bytes(), ReadMode::Strict).unwrap();
+        assert_eq!(sum_all_diffs::<u64, u64>(report.col1.clone(), report.col2.clone()), Ok(1));
+        assert!(get_similarity_measure::<u64, u64>(vec![u64::MAX; 2], report.col1.clone()).is_err());
+        assert_eq!(get_similarity_measure::<u64, i128>(vec![u64::MAX; 2], report.col1), Ok(2 * u64::MAX as i128));
+
+        assert_eq!(try_sum_of_diffs_from_file::<u64, u64>("data/wide_1.txt").unwrap(), 2);
+        assert_eq!(try_get_similarity_measure_from_file::<u64, i128>("data/wide_1.txt").unwrap(), 2 * u64::MAX as i128);
+        assert!(matches!(try_get_similarity_measure_from_file::<u64, u64>("data/wide_1.txt"), Err(ScoreError::Overflow(_))));
+        assert!(matches!(try_sum_of_diffs_from_file::<i32, i64>("data/wide_1.txt"), Err(ScoreError::Read(_))));
     }
 }
@@ -0,0 +1,209 @@
+use std::fmt;
+
+use crate::Location;
+
+/// Ways of measuring how far apart two equally long location lists are.
+///
+/// The `Sorted*` and `MaxDeviation` metrics compare the lists as distributions:
+/// both are sorted and zipped, like `sum_all_diffs` does. The rank metrics keep
+/// the row pairing and compare how each list orders those rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// Sum of `|a - b|` over the sorted lists.
+    SortedL1,
+    /// Euclidean distance between the sorted lists.
+    SortedL2,
+    /// Largest `|a - b|` over the sorted lists.
+    MaxDeviation,
+    /// Sum of `|rank_a - rank_b|` per row, ties getting their average rank.
+    SpearmanFootrule,
+    /// Number of row pairs the two lists order in opposite directions.
+    KendallTau,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 5] = [
+        Metric::SortedL1,
+        Metric::SortedL2,
+        Metric::MaxDeviation,
+        Metric::SpearmanFootrule,
+        Metric::KendallTau,
+    ];
+
+    pub fn distance<T: Location>(self, a: &[T], b: &[T]) -> Result<f64, LengthMismatch> {
+        check_lengths(a, b)?;
+        Ok(self.prepared_distance(&Prepared::new(a), &Prepared::new(b)))
+    }
+
+    fn prepared_distance<T: Location>(self, a: &Prepared<T>, b: &Prepared<T>) -> f64 {
+        let sorted_deltas = || a.sorted.iter().zip(b.sorted.iter()).map(|(&x, &y)| gap(x, y));
+        match self {
+            Metric::SortedL1 => sorted_deltas().sum(),
+            Metric::SortedL2 => (sorted_deltas().map(|d| d.powi(2)).sum::<f64>()).sqrt(),
+            Metric::MaxDeviation => sorted_deltas().fold(0.0, f64::max),
+            Metric::SpearmanFootrule => a.ranks.iter().zip(b.ranks.iter()).map(|(x, y)| (x - y).abs()).sum(),
+            Metric::KendallTau => discordant_pairs(a.values, b.values) as f64,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct LengthMismatch {
+    pub left: usize,
+    pub right: usize,
+}
+
+impl fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot compare lists of length {} and {}", self.left, self.right)
+    }
+}
+
+impl std::error::Error for LengthMismatch {}
+
+/// `|x - y|`, exact while it fits in `T` and rounded to `f64` otherwise.
+fn gap<T: Location>(x: T, y: T) -> f64 {
+    let (high, low) = if x > y { (x, y) } else { (y, x) };
+    let to_f64 = |value: T| value.to_f64().unwrap_or(f64::NAN);
+    high.checked_sub(&low).map_or_else(|| to_f64(high) - to_f64(low), to_f64)
+}
+
+fn check_lengths<T>(a: &[T], b: &[T]) -> Result<(), LengthMismatch> {
+    if a.len() != b.len() {
+        return Err(LengthMismatch { left: a.len(), right: b.len() });
+    }
+    Ok(())
+}
+
+/// A column together with its sorted copy and ranks, so a distance matrix
+/// sorts every column once rather than once per pair.
+struct Prepared<'a, T> {
+    values: &'a [T],
+    sorted: Vec<T>,
+    ranks: Vec<f64>,
+}
+
+impl<'a, T: Location> Prepared<'a, T> {
+    fn new(values: &'a [T]) -> Self {
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by_key(|&i| values[i]);
+        let sorted: Vec<T> = order.iter().map(|&i| values[i]).collect();
+
+        let mut ranks = vec![0.0; values.len()];
+        let mut start = 0;
+        while start < order.len() {
+            let end = start + sorted[start..].iter().take_while(|&&v| v == sorted[start]).count();
+            // Ranks are 1-based; a run of ties at positions start..end shares their mean.
+            let rank = (start + end + 1) as f64 / 2.0;
+            order[start..end].iter().for_each(|&i| ranks[i] = rank);
+            start = end;
+        }
+        Prepared { values, sorted, ranks }
+    }
+}
+
+/// Counts pairs `(i, j)` with `a[i] < a[j]` and `b[i] > b[j]` (or the other way
+/// round) in O(n log n): sort rows by `(a, b)` and count strict inversions in `b`.
+/// Pairs tied in either list are neither concordant nor discordant.
+fn discordant_pairs<T: Location>(a: &[T], b: &[T]) -> u64 {
+    let mut rows: Vec<(T, T)> = a.iter().copied().zip(b.iter().copied()).collect();
+    rows.sort();
+    let mut ys: Vec<T> = rows.into_iter().map(|(_, y)| y).collect();
+    let mut scratch = ys.clone();
+    count_inversions(&mut ys, &mut scratch)
+}
+
+fn count_inversions<T: Copy + Ord>(values: &mut [T], scratch: &mut [T]) -> u64 {
+    let n = values.len();
+    if n < 2 {
+        return 0;
+    }
+    let mid = n / 2;
+    let mut count = count_inversions(&mut values[..mid], &mut scratch[..mid])
+        + count_inversions(&mut values[mid..], &mut scratch[mid..]);
+    let (mut i, mut j, mut k) = (0, mid, 0);
+    while i < mid && j < n {
+        if values[i] <= values[j] {
+            scratch[k] = values[i];
+            i += 1;
+        } else {
+            scratch[k] = values[j];
+            count += (mid - i) as u64;
+            j += 1;
+        }
+        k += 1;
+    }
+    scratch[k..k + mid - i].copy_from_slice(&values[i..mid]);
+    k += mid - i;
+    scratch[k..].copy_from_slice(&values[j..]);
+    values.copy_from_slice(scratch);
+    count
+}
+
+/// Symmetric matrix of `metric` distances between every pair of `columns`.
+pub fn distance_matrix<T: Location>(columns: &[Vec<T>], metric: Metric) -> Result<Vec<Vec<f64>>, LengthMismatch> {
+    if let Some(first) = columns.first() {
+        columns.iter().try_for_each(|column| check_lengths(first, column))?;
+    }
+    let prepared: Vec<Prepared<T>> = columns.iter().map(|column| Prepared::new(column)).collect();
+    let n = columns.len();
+    let mut matrix = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let distance = metric.prepared_distance(&prepared[i], &prepared[j]);
+            matrix[i][j] = distance;
+            matrix[j][i] = distance;
+        }
+    }
+    Ok(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_n_columns, sum_all_diffs, ReadMode};
+
+    #[test]
+    fn sorted_l1_matches_sum_all_diffs() {
+        let a = vec![3, 4, 2, 1, 3, 3];
+        let b = vec![4, 3, 5, 3, 9, 3];
+        assert_eq!(Metric::SortedL1.distance(&a, &b), Ok(sum_all_diffs::<i32, i64>(a, b).unwrap() as f64));
+    }
+
+    #[test]
+    fn rank_metrics() {
+        let a = [1, 2, 3, 4];
+        let b = [4, 3, 2, 1];
+        assert_eq!(Metric::SpearmanFootrule.distance(&a, &b), Ok(8.0));
+        assert_eq!(Metric::KendallTau.distance(&a, &b), Ok(6.0));
+        assert_eq!(Metric::KendallTau.distance(&a, &a), Ok(0.0));
+        // Ties in either list are not discordant.
+        assert_eq!(Metric::KendallTau.distance(&[1, 1, 2], &[2, 1, 1]), Ok(1.0));
+    }
+
+    #[test]
+    fn extreme_values() {
+        let span = u32::MAX as f64;
+        let (low, high) = ([i32::MIN, i32::MIN], [i32::MAX, i32::MAX]);
+        assert_eq!(Metric::SortedL2.distance(&low, &high), Ok((2.0 * span * span).sqrt()));
+        assert_eq!(Metric::SortedL1.distance(&low, &high), Ok(2.0 * span));
+        assert_eq!(Metric::MaxDeviation.distance(&low, &high), Ok(span));
+        assert_eq!(Metric::SortedL1.distance(&[u64::MAX, 0], &[0, 1]), Ok((u64::MAX - 1) as f64));
+        assert_eq!(Metric::SortedL1.distance(&[i64::MIN], &[i64::MAX]), Ok(u64::MAX as f64));
+        assert_eq!(Metric::KendallTau.distance(&[1u8, 2, 3], &[3u8, 2, 1]), Ok(3.0));
+    }
+
+    #[test]
+    fn matrix_over_three_columns() {
+        let input = "1 2 9\n2 4 8\n3 6 7\n";
+        let columns = read_n_columns::<_, i32>(input.as_bytes(), 3, ReadMode::Strict).unwrap().columns;
+        let matrix = distance_matrix(&columns, Metric::MaxDeviation).unwrap();
+        assert_eq!(matrix, vec![
+            vec![0.0, 3.0, 6.0],
+            vec![3.0, 0.0, 5.0],
+            vec![6.0, 5.0, 0.0],
+        ]);
+        assert_eq!(distance_matrix(&columns, Metric::KendallTau).unwrap()[0][2], 3.0);
+        assert_eq!(Metric::SortedL1.distance(&[1], &[1, 2]), Err(LengthMismatch { left: 1, right: 2 }));
+    }
+}
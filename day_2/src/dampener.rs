@@ -0,0 +1,131 @@
+use crate::SafetyPolicy;
+
+/// Direction states tracked per kept level: no non-zero delta yet, decreasing, increasing.
+const DIRECTIONS: [i32; 3] = [0, -1, 1];
+
+fn state(direction: i32) -> usize {
+    (direction + 1) as usize
+}
+
+impl SafetyPolicy {
+    /// The fewest levels to remove, at most `max_removals`, so that what is
+    /// left follows the policy; `None` if no such set exists. Among equally
+    /// small sets, the one whose sorted indices come first is returned.
+    ///
+    /// Dynamic programming over (last kept level, direction so far). Since at
+    /// most `max_removals` levels can be skipped between two kept ones, each
+    /// state has O(k) successors and the whole search is O(n · k).
+    pub fn removals_to_make_safe(&self, report: &[i32], max_removals: usize) -> Option<Vec<usize>> {
+        let n = report.len();
+        if n == 0 {
+            return Some(Vec::new());
+        }
+        let k = max_removals.min(n - 1);
+
+        // cost[i][s]: fewest removals after level i, given i is kept with direction state s.
+        let mut cost = vec![[usize::MAX; 3]; n];
+        for i in (0..n).rev() {
+            for direction in DIRECTIONS {
+                let mut best = n - 1 - i;
+                for next in i + 1..=(i + k + 1).min(n - 1) {
+                    let delta = i64::from(report[next]) - i64::from(report[i]);
+                    if self.violation(delta, direction).is_some() {
+                        continue;
+                    }
+                    let after = cost[next][state(next_direction(direction, delta))];
+                    best = best.min((next - i - 1).saturating_add(after));
+                }
+                cost[i][state(direction)] = best;
+            }
+        }
+
+        let total = (0..=k).map(|first| first + cost[first][state(0)]).min()?;
+        if total > k {
+            return None;
+        }
+
+        // Walk forward, at each step taking the choice whose removed indices
+        // come first: dropping everything left beats skipping ahead, and a
+        // longer skip beats a shorter one.
+        let first = (0..=k).rev().find(|&first| first + cost[first][state(0)] == total)?;
+        let mut removed: Vec<usize> = (0..first).collect();
+        let (mut i, mut direction) = (first, 0);
+        loop {
+            let remaining = cost[i][state(direction)];
+            if remaining == n - 1 - i {
+                removed.extend(i + 1..n);
+                return Some(removed);
+            }
+            let next = (i + 1..=(i + k + 1).min(n - 1)).rev().find(|&next| {
+                let delta = i64::from(report[next]) - i64::from(report[i]);
+                self.violation(delta, direction).is_none()
+                    && (next - i - 1).saturating_add(cost[next][state(next_direction(direction, delta))]) == remaining
+            })?;
+            removed.extend(i + 1..next);
+            direction = next_direction(direction, i64::from(report[next]) - i64::from(report[i]));
+            i = next;
+        }
+    }
+}
+
+fn next_direction(direction: i32, delta: i64) -> i32 {
+    if direction == 0 { delta.signum() as i32 } else { direction }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::{DirectionMode, SafetyPolicy};
+
+    /// Every removal set of size 0..=k in lexicographic order, first match wins.
+    fn brute_force(policy: &SafetyPolicy, report: &[i32], k: usize) -> Option<Vec<usize>> {
+        fn search(policy: &SafetyPolicy, report: &[i32], k: usize, from: usize, removed: &mut Vec<usize>) -> bool {
+            if k == 0 {
+                let kept: Vec<i32> = (0..report.len()).filter(|i| !removed.contains(i)).map(|i| report[i]).collect();
+                return policy.check(&kept).is_safe();
+            }
+            for i in from..report.len() {
+                removed.push(i);
+                if search(policy, report, k - 1, i + 1, removed) {
+                    return true;
+                }
+                removed.pop();
+            }
+            false
+        }
+        (0..=k.min(report.len())).find_map(|size| {
+            let mut removed = Vec::new();
+            search(policy, report, size, 0, &mut removed).then_some(removed)
+        })
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let policies = [
+            SafetyPolicy::default(),
+            SafetyPolicy { min_delta: 0, max_delta: 2, direction: DirectionMode::Increasing, ..SafetyPolicy::default() },
+            SafetyPolicy { direction: DirectionMode::Any, ..SafetyPolicy::default() },
+        ];
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..9);
+            let report: Vec<i32> = (0..len).map(|_| rng.gen_range(0..12)).collect();
+            let k = rng.gen_range(0..4);
+            for policy in &policies {
+                assert_eq!(policy.removals_to_make_safe(&report, k), brute_force(policy, &report, k), "{:?}", report);
+            }
+        }
+    }
+
+    #[test]
+    fn long_report_with_several_removals() {
+        let mut report: Vec<i32> = (0..100_000).collect();
+        report[10] = -5;
+        report[500] = 7;
+        report[99_998] = 0;
+        assert_eq!(SafetyPolicy::default().removals_to_make_safe(&report, 3), Some(vec![10, 500, 99_998]));
+        assert_eq!(SafetyPolicy::default().removals_to_make_safe(&report, 2), None);
+    }
+}
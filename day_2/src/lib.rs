@@ -1,66 +1,92 @@
 use std::fs::File;
 use std::io::{self, BufRead};
 
+use serde::Serialize;
+
+mod dampener;
+pub mod policy;
+pub mod repair;
+pub mod segments;
+pub mod stream;
+
+pub use policy::{DirectionMode, SafetyPolicy};
+
 type AllReports = Vec<Vec<i32>>;
 
+/// Fails on a line that cannot be read, e.g. because it is not UTF-8, or
+/// that holds a token which is not a level, rather than analysing what is left.
 fn read_file_and_store_reports(file_path: &str) -> io::Result<AllReports> {
     // Open the file
     let file = File::open(file_path)?;
     let reader = io::BufReader::new(file);
 
     // Create the output vector
-    let output: AllReports = reader.lines().map(|line| {
-        let line: String = line.expect("");
-        let numbers: Vec<i32> = line
-            .split_whitespace()
-            .filter_map(|s| s.parse::<i32>().ok())
-            .collect();
-        numbers
-    }).collect();
-    Ok(output)
+    reader.lines().enumerate().map(|(i, line)| {
+        line?.split_whitespace()
+            .map(|s| s.parse::<i32>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: invalid level {:?}", i + 1, s))
+            }))
+            .collect()
+    }).collect()
 }
 
-fn is_report_safe(report: &Vec<i32>) -> bool {
-    let deltas: Vec<i32> = report
-    .windows(2)
-    .map(|pair| pair[1] - pair[0])
-    .collect();
-    deltas.iter().all(|element| {
-        let element = element.abs();
-        element >= 1 && element <= 3}) && (
-            deltas.iter().all(|&a| a <= 0) || deltas.iter().all(|&a| a >= 0))
+/// Which safety rule a pair of adjacent levels broke.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// The levels are closer than the policy's `min_delta`.
+    DeltaTooSmall,
+    /// The levels are further apart than the policy's `max_delta`.
+    DeltaTooLarge,
+    /// The report was increasing and now decreases, or the other way round.
+    DirectionChange,
+    /// The report moves in a direction the policy does not allow.
+    DirectionNotAllowed,
 }
 
-fn could_report_be_safe(report: &Vec<i32>) -> bool {
-    // If the report is already safe, return true
-    if is_report_safe(report) {
-        return true;
-    }
-    
-    // Try removing each level and check if the resulting report is safe
-    for i in 0..report.len() {
-        // Create a new report with the i-th element removed
-        let mut modified_report: Vec<i32> = report.clone();
-        modified_report.remove(i);
-        
-        // If the modified report is safe, return true
-        if is_report_safe(&modified_report) {
-            return true;
-        }
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SafetyVerdict {
+    Safe,
+    /// The first violation: `delta` is `report[index] - report[index - 1]`.
+    Unsafe { index: usize, delta: i64, rule: Rule },
+    /// Unsafe as reported, but safe once the levels at `removed` are dropped.
+    Dampened { removed: Vec<usize> },
+}
+
+impl SafetyVerdict {
+    pub fn is_safe(&self) -> bool {
+        !matches!(self, SafetyVerdict::Unsafe { .. })
     }
-    
-    // If no modification makes the report safe, return false
-    false
+}
+
+/// The verdict under the default policy, without the dampener.
+pub fn report_verdict(report: &[i32]) -> SafetyVerdict {
+    SafetyPolicy::default().check(report)
+}
+
+/// The verdict under the default policy, with the dampener.
+pub fn dampened_verdict(report: &[i32]) -> SafetyVerdict {
+    SafetyPolicy::default().verdict(report)
 }
 
 pub fn count_safe_reports_in_file(file_path: &str) -> usize {
-    let reports: AllReports = read_file_and_store_reports(file_path).expect("");
-    reports.iter().filter(|&report| is_report_safe(report)).count()
+    count_safe_reports_in_file_with_policy(file_path, &SafetyPolicy::default())
 }
 
 pub fn count_potentially_safe_reports_in_file(file_path: &str) -> usize {
+    count_potentially_safe_reports_in_file_with_policy(file_path, &SafetyPolicy::default())
+}
+
+/// `count_safe_reports_in_file` under `policy`.
+pub fn count_safe_reports_in_file_with_policy(file_path: &str, policy: &SafetyPolicy) -> usize {
+    let reports: AllReports = read_file_and_store_reports(file_path).expect("");
+    reports.iter().filter(|&report| policy.check(report) == SafetyVerdict::Safe).count()
+}
+
+/// `count_potentially_safe_reports_in_file` under `policy`.
+pub fn count_potentially_safe_reports_in_file_with_policy(file_path: &str, policy: &SafetyPolicy) -> usize {
     let reports: AllReports = read_file_and_store_reports(file_path).expect("");
-    reports.iter().filter(|&report| could_report_be_safe(report)).count()
+    reports.iter().filter(|&report| policy.verdict(report).is_safe()).count()
 }
 
 #[cfg(test)]
@@ -73,5 +99,41 @@ mod tests {
         assert_eq!(count_safe_reports_in_file("data/input_1.txt"), 224);
         assert_eq!(count_potentially_safe_reports_in_file("data/example_1.txt"), 4);
         assert_eq!(count_potentially_safe_reports_in_file("data/input_1.txt"), 293);
+
+        let lenient = SafetyPolicy { max_delta: 4, tolerated_removals: 0, ..SafetyPolicy::default() };
+        assert_eq!(count_safe_reports_in_file_with_policy("data/example_1.txt", &lenient), 3);
+        assert_eq!(count_potentially_safe_reports_in_file_with_policy("data/example_1.txt", &lenient), 3);
+    }
+
+    #[test]
+    fn verdicts() {
+        assert_eq!(report_verdict(&[7, 6, 4, 2, 1]), SafetyVerdict::Safe);
+        assert_eq!(report_verdict(&[1, 2, 7, 8, 9]),
+                   SafetyVerdict::Unsafe { index: 2, delta: 5, rule: Rule::DeltaTooLarge });
+        assert_eq!(report_verdict(&[1, 3, 2, 4, 5]),
+                   SafetyVerdict::Unsafe { index: 2, delta: -1, rule: Rule::DirectionChange });
+        assert_eq!(report_verdict(&[8, 6, 4, 4, 1]),
+                   SafetyVerdict::Unsafe { index: 3, delta: 0, rule: Rule::DeltaTooSmall });
+
+        assert_eq!(dampened_verdict(&[1, 3, 2, 4, 5]), SafetyVerdict::Dampened { removed: vec![1] });
+        assert_eq!(dampened_verdict(&[8, 6, 4, 4, 1]), SafetyVerdict::Dampened { removed: vec![2] });
+        assert_eq!(dampened_verdict(&[9, 7, 6, 2, 1]),
+                   SafetyVerdict::Unsafe { index: 3, delta: -4, rule: Rule::DeltaTooLarge });
+    }
+
+    #[test]
+    fn extreme_levels() {
+        let span = i64::from(i32::MAX) - i64::from(i32::MIN);
+        assert_eq!(report_verdict(&[i32::MIN, i32::MAX]),
+                   SafetyVerdict::Unsafe { index: 1, delta: span, rule: Rule::DeltaTooLarge });
+        assert_eq!(dampened_verdict(&[i32::MAX, i32::MIN, i32::MIN + 1]), SafetyVerdict::Dampened { removed: vec![0] });
+        let wide = SafetyPolicy { max_delta: i32::MAX, direction: DirectionMode::Any, ..SafetyPolicy::default() };
+        assert_eq!(wide.check(&[i32::MIN, i32::MAX]),
+                   SafetyVerdict::Unsafe { index: 1, delta: span, rule: Rule::DeltaTooLarge });
+        assert_eq!(wide.check(&[i32::MIN, -1, i32::MAX - 1]), SafetyVerdict::Safe);
+        let report = [i32::MIN, i32::MAX, i32::MIN, i32::MAX];
+        assert_eq!(wide.analyze_segments(&report).longest_run, 0..1);
+        let plan = wide.plan_repair(&report, &repair::RepairCosts::default()).unwrap();
+        assert_eq!((plan.cost, wide.check(&plan.repaired)), (2, SafetyVerdict::Safe));
     }
 }
@@ -0,0 +1,189 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{Rule, SafetyVerdict};
+
+/// Which way the levels of a safe report may move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectionMode {
+    Increasing,
+    Decreasing,
+    /// Increasing or decreasing, but the same way throughout.
+    Either,
+    /// No constraint on direction.
+    Any,
+}
+
+/// The rules a report has to follow to be safe. The default is the puzzle's:
+/// adjacent levels 1 to 3 apart, one direction throughout, and one level
+/// the problem dampener may remove.
+///
+/// A policy file is TOML with any of the fields, the rest keeping their defaults:
+///
+/// ```toml
+/// min_delta = 1
+/// max_delta = 5
+/// direction = "increasing"
+/// tolerated_removals = 2
+/// ```
+///
+/// or the same fields as a JSON object.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafetyPolicy {
+    pub min_delta: i32,
+    pub max_delta: i32,
+    pub direction: DirectionMode,
+    /// How many levels the dampener may remove to make a report safe.
+    pub tolerated_removals: usize,
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        SafetyPolicy { min_delta: 1, max_delta: 3, direction: DirectionMode::Either, tolerated_removals: 1 }
+    }
+}
+
+#[derive(Debug)]
+pub enum PolicyError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Io(error) => write!(f, "cannot read policy: {}", error),
+            PolicyError::Parse(error) => write!(f, "cannot parse policy: {}", error),
+            PolicyError::Json(error) => write!(f, "cannot parse policy: {}", error),
+            PolicyError::Invalid(reason) => write!(f, "invalid policy: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+impl SafetyPolicy {
+    pub fn from_toml_str(text: &str) -> Result<Self, PolicyError> {
+        let policy: SafetyPolicy = toml::from_str(text).map_err(PolicyError::Parse)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn from_json_str(text: &str) -> Result<Self, PolicyError> {
+        let policy: SafetyPolicy = serde_json::from_str(text).map_err(PolicyError::Json)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Reads a `.json` file as JSON and any other file as TOML.
+    pub fn from_file(file_path: &str) -> Result<Self, PolicyError> {
+        let text = fs::read_to_string(file_path).map_err(PolicyError::Io)?;
+        match Path::new(file_path).extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::from_json_str(&text),
+            _ => Self::from_toml_str(&text),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), PolicyError> {
+        if self.min_delta < 0 || self.min_delta > self.max_delta {
+            return Err(PolicyError::Invalid(format!(
+                "delta range {}..={} is empty or negative", self.min_delta, self.max_delta
+            )));
+        }
+        Ok(())
+    }
+
+    /// The rule `delta` breaks, if any, given the direction of the report so
+    /// far (the sign of its first non-zero delta, or 0 if there is none yet).
+    pub(crate) fn violation(&self, delta: i64, direction: i32) -> Option<Rule> {
+        if delta.abs() < i64::from(self.min_delta) {
+            return Some(Rule::DeltaTooSmall);
+        }
+        if delta.abs() > i64::from(self.max_delta) {
+            return Some(Rule::DeltaTooLarge);
+        }
+        match self.direction {
+            DirectionMode::Increasing if delta < 0 => Some(Rule::DirectionNotAllowed),
+            DirectionMode::Decreasing if delta > 0 => Some(Rule::DirectionNotAllowed),
+            DirectionMode::Either if direction != 0 && delta != 0 && delta.signum() != i64::from(direction) => {
+                Some(Rule::DirectionChange)
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks the report as given, without removing any level.
+    pub fn check(&self, report: &[i32]) -> SafetyVerdict {
+        let mut direction = 0;
+        for (i, pair) in report.windows(2).enumerate() {
+            let delta = i64::from(pair[1]) - i64::from(pair[0]);
+            if let Some(rule) = self.violation(delta, direction) {
+                return SafetyVerdict::Unsafe { index: i + 1, delta, rule };
+            }
+            if direction == 0 {
+                direction = delta.signum() as i32;
+            }
+        }
+        SafetyVerdict::Safe
+    }
+
+    /// Like `check`, but a report that becomes safe after removing at most
+    /// `tolerated_removals` levels is `Dampened` rather than `Unsafe`. An
+    /// unsafe verdict still describes the first violation in the report as given.
+    pub fn verdict(&self, report: &[i32]) -> SafetyVerdict {
+        let verdict = self.check(report);
+        if verdict.is_safe() {
+            return verdict;
+        }
+        match self.removals_to_make_safe(report, self.tolerated_removals) {
+            Some(removed) => SafetyVerdict::Dampened { removed },
+            None => verdict,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_applies_policy() {
+        let policy = SafetyPolicy::from_toml_str("max_delta = 5\ndirection = \"increasing\"\ntolerated_removals = 2").unwrap();
+        assert_eq!(policy, SafetyPolicy {
+            min_delta: 1,
+            max_delta: 5,
+            direction: DirectionMode::Increasing,
+            tolerated_removals: 2,
+        });
+        assert_eq!(policy.check(&[1, 2, 7, 8, 9]), SafetyVerdict::Safe);
+        assert_eq!(policy.check(&[7, 6, 4, 2, 1]),
+                   SafetyVerdict::Unsafe { index: 1, delta: -1, rule: Rule::DirectionNotAllowed });
+        assert_eq!(policy.verdict(&[1, 9, 2, 0, 3]), SafetyVerdict::Dampened { removed: vec![1, 3] });
+
+        assert!(matches!(SafetyPolicy::from_toml_str("min_delta = 4"), Err(PolicyError::Invalid(_))));
+        assert!(matches!(SafetyPolicy::from_toml_str("max_gap = 4"), Err(PolicyError::Parse(_))));
+    }
+
+    #[test]
+    fn reads_json_policies() {
+        let json = r#"{"max_delta": 5, "direction": "increasing", "tolerated_removals": 2}"#;
+        let policy = SafetyPolicy::from_json_str(json).unwrap();
+        assert_eq!(policy, SafetyPolicy::from_toml_str("max_delta = 5\ndirection = \"increasing\"\ntolerated_removals = 2").unwrap());
+        assert!(matches!(SafetyPolicy::from_json_str(r#"{"min_delta": 4}"#), Err(PolicyError::Invalid(_))));
+        assert!(matches!(SafetyPolicy::from_json_str(r#"{"max_gap": 4}"#), Err(PolicyError::Json(_))));
+
+        let path = std::env::temp_dir().join(format!("day_2_policy_{}.json", std::process::id()));
+        fs::write(&path, json).unwrap();
+        let from_file = SafetyPolicy::from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(from_file.unwrap(), policy);
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{DirectionMode, SafetyPolicy, SafetyVerdict};
+
+/// What each kind of edit costs the repair planner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RepairCosts {
+    /// Cost of dropping a level.
+    pub removal: u64,
+    /// Flat cost of changing a level's value.
+    pub adjustment: u64,
+    /// Extra cost per unit a level's value is moved.
+    pub per_unit: u64,
+}
+
+impl Default for RepairCosts {
+    fn default() -> Self {
+        RepairCosts { removal: 1, adjustment: 1, per_unit: 0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Remove { index: usize },
+    Adjust { index: usize, from: i32, to: i32 },
+}
+
+/// Longest unsafe report `plan_repair` takes on; its cost grows with the
+/// fourth power of the length, and this many levels take about half a second.
+pub const MAX_REPAIR_LEVELS: usize = 100;
+
+/// The report is unsafe and longer than `MAX_REPAIR_LEVELS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReportTooLong {
+    pub levels: usize,
+}
+
+impl fmt::Display for ReportTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot plan a repair for {} levels, at most {} are supported", self.levels, MAX_REPAIR_LEVELS)
+    }
+}
+
+impl std::error::Error for ReportTooLong {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepairPlan {
+    /// Edits in ascending order of level index.
+    pub edits: Vec<Edit>,
+    pub cost: u64,
+    /// The report after applying `edits`; it follows the policy.
+    pub repaired: Vec<i32>,
+}
+
+/// Last kept value and the direction so far; `None` while nothing is kept.
+type State = Option<(i64, i32)>;
+
+type Score = (u64, usize);
+
+/// Scored values in ascending order, answering "cheapest value in a range"
+/// with a sparse table; ties go to the smallest value.
+struct RangeMin {
+    values: Vec<i64>,
+    scores: Vec<Score>,
+    /// `table[k][i]`: position of the cheapest of the `2^k` values from `i`.
+    table: Vec<Vec<usize>>,
+}
+
+impl RangeMin {
+    fn new(entries: Vec<(i64, Score)>) -> Self {
+        let (values, scores): (Vec<i64>, Vec<Score>) = entries.into_iter().unzip();
+        let cheaper = |a: usize, b: usize| if scores[b] < scores[a] { b } else { a };
+        let mut table = vec![(0..values.len()).collect::<Vec<usize>>()];
+        while 2 << (table.len() - 1) <= values.len() {
+            let half = 1 << (table.len() - 1);
+            let last = table.last().unwrap();
+            let row = (0..=values.len() - 2 * half).map(|i| cheaper(last[i], last[i + half])).collect();
+            table.push(row);
+        }
+        RangeMin { values, scores, table }
+    }
+
+    fn min(&self, low: i64, high: i64) -> Option<(i64, Score)> {
+        let start = self.values.partition_point(|&value| value < low);
+        let end = self.values.partition_point(|&value| value <= high);
+        if start >= end {
+            return None;
+        }
+        let k = (end - start).ilog2() as usize;
+        let (a, b) = (self.table[k][start], self.table[k][end - (1 << k)]);
+        let best = if self.scores[b] < self.scores[a] { b } else { a };
+        Some((self.values[best], self.scores[best]))
+    }
+}
+
+impl SafetyPolicy {
+    /// The cheapest set of edits that makes `report` follow this policy, ties
+    /// going to the plan with fewer edits. At least one level is always kept.
+    /// `tolerated_removals` does not apply here; removals are limited by cost only.
+    ///
+    /// Dynamic programming over (last kept value, direction so far). Some
+    /// cheapest plan keeps every level either unchanged or at a value pinned,
+    /// through a chain of deltas at their limits, to a level that is kept
+    /// unchanged (or to the edge of the `i32` range). Only those values are
+    /// tried, and the cheapest state a value can follow is looked up by range
+    /// rather than by trying every delta, so neither `max_delta` nor how far
+    /// apart the levels are matters. For `n` levels there are O(n³) such
+    /// values per level, so the whole plan takes O(n⁴ log n) time and O(n⁴)
+    /// memory; unsafe reports longer than `MAX_REPAIR_LEVELS` are rejected.
+    pub fn plan_repair(&self, report: &[i32], costs: &RepairCosts) -> Result<RepairPlan, ReportTooLong> {
+        if report.is_empty() || self.check(report) == SafetyVerdict::Safe {
+            return Ok(RepairPlan { edits: Vec::new(), cost: 0, repaired: report.to_vec() });
+        }
+        if report.len() > MAX_REPAIR_LEVELS {
+            return Err(ReportTooLong { levels: report.len() });
+        }
+        // Deltas that change the direction state the same way: zero, rising, falling.
+        let (min_delta, max_delta) = (self.min_delta as i64, self.max_delta as i64);
+        let smallest_move = min_delta.max(1);
+        let pieces: Vec<(i64, i64)> = [(0, 0), (smallest_move, max_delta), (-max_delta, -smallest_move)]
+            .into_iter()
+            .filter(|&(low, high)| low <= high && low.abs().max(high.abs()) >= min_delta)
+            .collect();
+
+        let mut best: BTreeMap<State, Score> = BTreeMap::from([(None, (0, 0))]);
+        // For each level, the states reached by keeping it and the state kept
+        // before; any other state was carried over by removing the level.
+        let mut steps: Vec<BTreeMap<State, State>> = Vec::with_capacity(report.len());
+        for (i, &level) in report.iter().enumerate() {
+            let mut by_direction: [Vec<(i64, Score)>; 3] = Default::default();
+            for (&state, &score) in &best {
+                if let Some((value, direction)) = state {
+                    by_direction[(direction + 1) as usize].push((value, score));
+                }
+            }
+            let by_direction = by_direction.map(RangeMin::new);
+
+            let mut next: BTreeMap<State, Score> = best.iter()
+                .map(|(&state, &(cost, edits))| (state, (cost.saturating_add(costs.removal), edits + 1)))
+                .collect();
+            let mut back = BTreeMap::new();
+            for value in self.repair_candidates(report, i) {
+                let (extra, edited) = if value == level as i64 {
+                    (0, 0)
+                } else {
+                    let moved = costs.per_unit.saturating_mul((value - level as i64).unsigned_abs());
+                    (costs.adjustment.saturating_add(moved), 1)
+                };
+                let mut relax = |state: State, (cost, edits): Score, from: State| {
+                    let score = (cost.saturating_add(extra), edits + edited);
+                    if next.get(&state).is_none_or(|&current| score < current) {
+                        next.insert(state, score);
+                        back.insert(state, from);
+                    }
+                };
+                if let Some(&score) = best.get(&None) {
+                    relax(Some((value, 0)), score, None);
+                }
+                for (direction, states) in (-1..=1).zip(&by_direction) {
+                    for &(low, high) in &pieces {
+                        if self.violation(low, direction).is_some() {
+                            continue;
+                        }
+                        if let Some((previous, score)) = states.min(value - high, value - low) {
+                            let turned = if direction == 0 { low.signum() as i32 } else { direction };
+                            relax(Some((value, turned)), score, Some((previous, direction)));
+                        }
+                    }
+                }
+            }
+            best = next;
+            steps.push(back);
+        }
+
+        let (&(mut state), &(cost, _)) = best.iter().filter(|(state, _)| state.is_some())
+            .min_by_key(|(_, &score)| score)
+            .expect("keeping the first level unchanged and removing the rest is always possible");
+        let mut edits = Vec::new();
+        let mut repaired = Vec::new();
+        for (index, back) in steps.iter().enumerate().rev() {
+            match (back.get(&state), state) {
+                (Some(&from), Some((value, _))) => {
+                    let value = value as i32;
+                    if value != report[index] {
+                        edits.push(Edit::Adjust { index, from: report[index], to: value });
+                    }
+                    repaired.push(value);
+                    state = from;
+                }
+                _ => edits.push(Edit::Remove { index }),
+            }
+        }
+        edits.reverse();
+        repaired.reverse();
+        Ok(RepairPlan { edits, cost, repaired })
+    }
+
+    /// The sorted values level `i` may be kept at: each level `j` moved by
+    /// `p · min_delta + q · max_delta` with `|p| + |q| <= |i - j|`, and the
+    /// same from both ends of the `i32` range. Unless any direction is
+    /// allowed, a chain of deltas cannot change sign, so neither can `p` and `q`.
+    fn repair_candidates(&self, report: &[i32], i: usize) -> Vec<i64> {
+        let (min_delta, max_delta) = (self.min_delta as i64, self.max_delta as i64);
+        let mixed_signs = self.direction == DirectionMode::Any;
+        let edges = [i32::MIN, i32::MAX].map(|edge| (edge as i64, report.len() - 1));
+        let anchors = report.iter().enumerate().map(|(j, &level)| (level as i64, i.abs_diff(j))).chain(edges);
+        let mut values = Vec::new();
+        for (anchor, steps) in anchors {
+            for p in 0..=steps as i64 {
+                for q in 0..=steps as i64 - p {
+                    let (low, high) = (p * min_delta, q * max_delta);
+                    values.extend([anchor + low + high, anchor - low - high]);
+                    if mixed_signs {
+                        values.extend([anchor + low - high, anchor - low + high]);
+                    }
+                }
+            }
+        }
+        values.retain(|&value| (i32::MIN as i64..=i32::MAX as i64).contains(&value));
+        values.sort_unstable();
+        values.dedup();
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    /// Cheapest (cost, edits) trying every value within `n · max_delta` of the levels.
+    fn reference(policy: &SafetyPolicy, report: &[i32], costs: &RepairCosts) -> (u64, usize) {
+        let spread = report.len() as i32 * policy.max_delta;
+        let low = report.iter().min().unwrap() - spread;
+        let high = report.iter().max().unwrap() + spread;
+        let mut best: BTreeMap<State, (u64, usize)> = BTreeMap::from([(None, (0, 0))]);
+        for &level in report {
+            let mut next: BTreeMap<State, (u64, usize)> = BTreeMap::new();
+            let mut relax = |state: State, score: (u64, usize)| {
+                let entry = next.entry(state).or_insert(score);
+                *entry = (*entry).min(score);
+            };
+            for (&from, &(cost, edits)) in &best {
+                relax(from, (cost + costs.removal, edits + 1));
+                for value in low..=high {
+                    let direction = match from {
+                        None => 0,
+                        Some((previous, direction)) => {
+                            let delta = i64::from(value) - previous;
+                            if policy.violation(delta, direction).is_some() {
+                                continue;
+                            }
+                            if direction == 0 { delta.signum() as i32 } else { direction }
+                        }
+                    };
+                    let score = if value == level {
+                        (cost, edits)
+                    } else {
+                        (cost + costs.adjustment + costs.per_unit * value.abs_diff(level) as u64, edits + 1)
+                    };
+                    relax(Some((value as i64, direction)), score);
+                }
+            }
+            best = next;
+        }
+        best.into_iter().filter(|(state, _)| state.is_some()).map(|(_, score)| score).min().unwrap()
+    }
+
+    #[test]
+    fn matches_reference() {
+        let mut rng = StdRng::seed_from_u64(10);
+        let directions = [DirectionMode::Increasing, DirectionMode::Decreasing, DirectionMode::Either, DirectionMode::Any];
+        for _ in 0..300 {
+            let min_delta = rng.gen_range(0..3);
+            let policy = SafetyPolicy {
+                min_delta,
+                max_delta: rng.gen_range(min_delta.max(1)..5),
+                direction: directions[rng.gen_range(0..4)],
+                ..SafetyPolicy::default()
+            };
+            let costs = RepairCosts {
+                removal: rng.gen_range(0..6),
+                adjustment: rng.gen_range(0..4),
+                per_unit: rng.gen_range(0..3),
+            };
+            let report: Vec<i32> = (0..rng.gen_range(1..6)).map(|_| rng.gen_range(0..12)).collect();
+            let plan = policy.plan_repair(&report, &costs).unwrap();
+            assert_eq!(policy.check(&plan.repaired), SafetyVerdict::Safe);
+            assert_eq!((plan.cost, plan.edits.len()), reference(&policy, &report, &costs), "{:?} {:?} {:?}", policy, costs, report);
+        }
+    }
+
+    #[test]
+    fn huge_deltas_and_costs() {
+        let policy = SafetyPolicy { min_delta: 1000, max_delta: i32::MAX, ..SafetyPolicy::default() };
+        let plan = policy.plan_repair(&[0, 5, 10, 3000, 2500, 9000, 1, 70_000], &RepairCosts::default()).unwrap();
+        assert_eq!(policy.check(&plan.repaired), SafetyVerdict::Safe);
+        assert_eq!(plan.cost, 4);
+
+        let costs = RepairCosts { removal: u64::MAX, adjustment: u64::MAX, per_unit: u64::MAX };
+        let plan = SafetyPolicy::default().plan_repair(&[1, 2, 10], &costs).unwrap();
+        assert_eq!(plan.cost, u64::MAX);
+        assert_eq!(plan.edits.len(), 1);
+    }
+
+    #[test]
+    fn long_reports() {
+        let mut report: Vec<i32> = (0..1000).collect();
+        assert_eq!(SafetyPolicy::default().plan_repair(&report, &RepairCosts::default()).unwrap().cost, 0);
+        report[500] = 0;
+        assert_eq!(SafetyPolicy::default().plan_repair(&report, &RepairCosts::default()), Err(ReportTooLong { levels: 1000 }));
+    }
+
+    #[test]
+    fn plans_cheapest_repair() {
+        let policy = SafetyPolicy::default();
+        let plan = policy.plan_repair(&[7, 6, 4, 2, 1], &RepairCosts::default()).unwrap();
+        assert_eq!(plan, RepairPlan { edits: Vec::new(), cost: 0, repaired: vec![7, 6, 4, 2, 1] });
+
+        // Two removals would be needed, a single adjustment is enough.
+        let plan = policy.plan_repair(&[1, 2, 7, 8, 9], &RepairCosts::default()).unwrap();
+        assert_eq!((plan.cost, plan.edits.len()), (1, 1));
+        assert!(matches!(plan.edits[0], Edit::Adjust { .. }));
+        assert_eq!(policy.check(&plan.repaired), SafetyVerdict::Safe);
+
+        let costs = RepairCosts { removal: 1, adjustment: 5, per_unit: 0 };
+        let plan = policy.plan_repair(&[1, 3, 2, 4, 5], &costs).unwrap();
+        assert_eq!(plan.cost, 1);
+        assert!(matches!(plan.edits[..], [Edit::Remove { .. }]));
+        assert_eq!(plan.repaired.len(), 4);
+
+        let costs = RepairCosts { removal: 100, adjustment: 0, per_unit: 1 };
+        let plan = policy.plan_repair(&[1, 2, 10], &costs).unwrap();
+        assert_eq!(plan, RepairPlan {
+            edits: vec![Edit::Adjust { index: 2, from: 10, to: 5 }],
+            cost: 5,
+            repaired: vec![1, 2, 5],
+        });
+    }
+
+    #[test]
+    fn widely_spread_levels() {
+        let policy = SafetyPolicy::default();
+        let plan = policy.plan_repair(&[0, 2_000_000_000, 1], &RepairCosts::default()).unwrap();
+        assert_eq!(plan, RepairPlan { edits: vec![Edit::Remove { index: 1 }], cost: 1, repaired: vec![0, 1] });
+        let plan = policy.plan_repair(&[i32::MAX, 0, i32::MAX - 1], &RepairCosts::default()).unwrap();
+        assert_eq!(plan.repaired, vec![i32::MAX, i32::MAX - 1]);
+
+        // Removing the outlier leaves a gap of 7 that still needs an adjustment of 4.
+        let costs = RepairCosts { removal: 100, adjustment: 0, per_unit: 1 };
+        let plan = policy.plan_repair(&[0, 2_000_000_000, 7], &costs).unwrap();
+        assert_eq!((plan.cost, plan.edits.len()), (104, 2));
+        assert_eq!(policy.check(&plan.repaired), SafetyVerdict::Safe);
+    }
+}
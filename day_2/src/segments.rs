@@ -0,0 +1,203 @@
+use std::io;
+use std::ops::Range;
+
+use crate::{read_file_and_store_reports, SafetyPolicy};
+
+/// Where a report is healthy under a policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentAnalysis {
+    /// The longest run of adjacent levels that is safe on its own; the
+    /// earliest one if there are several.
+    pub longest_run: Range<usize>,
+    /// Indices of one longest safe subsequence, i.e. the most levels that
+    /// can be kept by removing the others.
+    pub longest_subsequence: Vec<usize>,
+}
+
+/// Segment lengths summed and maximised over every report in a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SegmentStats {
+    pub reports: usize,
+    pub levels: usize,
+    pub total_run_length: usize,
+    pub max_run_length: usize,
+    pub total_subsequence_length: usize,
+    pub max_subsequence_length: usize,
+}
+
+impl SegmentStats {
+    pub fn add(&mut self, report_len: usize, analysis: &SegmentAnalysis) {
+        self.reports += 1;
+        self.levels += report_len;
+        self.total_run_length += analysis.longest_run.len();
+        self.max_run_length = self.max_run_length.max(analysis.longest_run.len());
+        self.total_subsequence_length += analysis.longest_subsequence.len();
+        self.max_subsequence_length = self.max_subsequence_length.max(analysis.longest_subsequence.len());
+    }
+
+    pub fn mean_run_length(&self) -> f64 {
+        self.total_run_length as f64 / self.reports.max(1) as f64
+    }
+
+    pub fn mean_subsequence_length(&self) -> f64 {
+        self.total_subsequence_length as f64 / self.reports.max(1) as f64
+    }
+
+    /// Share of all levels that fall inside their report's longest safe run.
+    pub fn run_coverage(&self) -> f64 {
+        self.total_run_length as f64 / self.levels.max(1) as f64
+    }
+}
+
+impl SafetyPolicy {
+    pub fn analyze_segments(&self, report: &[i32]) -> SegmentAnalysis {
+        SegmentAnalysis {
+            longest_run: self.longest_safe_run(report),
+            longest_subsequence: self.longest_safe_subsequence(report),
+        }
+    }
+
+    fn longest_safe_run(&self, report: &[i32]) -> Range<usize> {
+        if report.is_empty() {
+            return 0..0;
+        }
+        let mut best = 0..1;
+        // `flat_from` is where the equal levels at the end of the run begin.
+        let (mut start, mut direction, mut flat_from) = (0, 0, 0);
+        for i in 1..report.len() {
+            let delta = i64::from(report[i]) - i64::from(report[i - 1]);
+            if self.violation(delta, direction).is_some() {
+                // The pair may still open a new run, along with the equal
+                // levels before it, e.g. after a change of direction;
+                // otherwise the run restarts at `i`.
+                (start, direction) = if self.violation(delta, 0).is_some() { (i, 0) } else { (flat_from, 0) };
+            }
+            if delta != 0 || start == i {
+                flat_from = i;
+            }
+            if start < i && direction == 0 {
+                direction = delta.signum() as i32;
+            }
+            if i + 1 - start > best.len() {
+                best = start..i + 1;
+            }
+        }
+        best
+    }
+
+    /// O(n²) dynamic programming over (last kept level, direction so far).
+    fn longest_safe_subsequence(&self, report: &[i32]) -> Vec<usize> {
+        let n = report.len();
+        // length[i][d + 1]: longest safe subsequence ending at i with direction d.
+        let mut length = vec![[0usize; 3]; n];
+        let mut previous = vec![[None::<(usize, usize)>; 3]; n];
+        for i in 0..n {
+            length[i][1] = 1;
+            for j in 0..i {
+                let delta = i64::from(report[i]) - i64::from(report[j]);
+                for d in 0..3 {
+                    let direction = d as i32 - 1;
+                    if length[j][d] == 0 || self.violation(delta, direction).is_some() {
+                        continue;
+                    }
+                    let next = if direction == 0 { delta.signum() as i32 } else { direction };
+                    let slot = (next + 1) as usize;
+                    if length[j][d] + 1 > length[i][slot] {
+                        length[i][slot] = length[j][d] + 1;
+                        previous[i][slot] = Some((j, d));
+                    }
+                }
+            }
+        }
+        let best = (0..n).flat_map(|i| (0..3).map(move |d| (i, d)))
+            .max_by_key(|&(i, d)| (length[i][d], std::cmp::Reverse(i)));
+        let mut indices = Vec::new();
+        let mut cursor = best;
+        while let Some((i, d)) = cursor {
+            indices.push(i);
+            cursor = previous[i][d];
+        }
+        indices.reverse();
+        indices
+    }
+}
+
+pub fn segment_stats_in_file(file_path: &str, policy: &SafetyPolicy) -> io::Result<SegmentStats> {
+    let mut stats = SegmentStats::default();
+    for report in read_file_and_store_reports(file_path)? {
+        stats.add(report.len(), &policy.analyze_segments(&report));
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{DirectionMode, SafetyVerdict};
+
+    /// The earliest of the longest windows that `check` accepts.
+    fn brute_force_run(policy: &SafetyPolicy, report: &[i32]) -> Range<usize> {
+        (1..=report.len()).rev()
+            .find_map(|len| {
+                (0..=report.len() - len).find(|&start| policy.check(&report[start..start + len]) == SafetyVerdict::Safe)
+                    .map(|start| start..start + len)
+            })
+            .unwrap_or(0..0)
+    }
+
+    #[test]
+    fn segments_of_unsafe_reports() {
+        let policy = SafetyPolicy::default();
+        assert_eq!(policy.analyze_segments(&[1, 2, 7, 8, 9]), SegmentAnalysis {
+            longest_run: 2..5,
+            longest_subsequence: vec![2, 3, 4],
+        });
+        assert_eq!(policy.analyze_segments(&[1, 3, 2, 4, 5]), SegmentAnalysis {
+            longest_run: 2..5,
+            longest_subsequence: vec![0, 1, 3, 4],
+        });
+        assert_eq!(policy.analyze_segments(&[5, 5, 5]).longest_run, 0..1);
+        assert_eq!(policy.analyze_segments(&[]), SegmentAnalysis { longest_run: 0..0, longest_subsequence: vec![] });
+    }
+
+    #[test]
+    fn runs_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let policies = [
+            SafetyPolicy::default(),
+            SafetyPolicy { min_delta: 0, ..SafetyPolicy::default() },
+            SafetyPolicy { min_delta: 0, max_delta: 2, direction: DirectionMode::Increasing, ..SafetyPolicy::default() },
+            SafetyPolicy { min_delta: 0, direction: DirectionMode::Any, ..SafetyPolicy::default() },
+        ];
+        assert_eq!(policies[1].analyze_segments(&[1, 2, 2, 2, 1, 0]).longest_run, 1..6);
+        for _ in 0..2000 {
+            let report: Vec<i32> = (0..rng.gen_range(0..10)).map(|_| rng.gen_range(0..5)).collect();
+            for policy in &policies {
+                assert_eq!(policy.longest_safe_run(&report), brute_force_run(policy, &report), "{:?}", report);
+            }
+        }
+    }
+
+    #[test]
+    fn stats_over_example() {
+        let stats = segment_stats_in_file("data/example_1.txt", &SafetyPolicy::default()).unwrap();
+        assert_eq!(stats.reports, 6);
+        assert_eq!(stats.levels, 30);
+        assert_eq!(stats.max_run_length, 5);
+        assert_eq!(stats.max_subsequence_length, 5);
+        // Reports that are safe after one removal keep four levels.
+        assert_eq!(stats.total_subsequence_length, 5 + 3 + 3 + 4 + 4 + 5);
+
+        let path = std::env::temp_dir().join(format!("day_2_segments_{}.txt", std::process::id()));
+        for (contents, message) in [(&b"1 2 3\n4 x 5\n"[..], "line 2: invalid level \"x\""), (b"1 2 3\n\xff 4\n", "")] {
+            std::fs::write(&path, contents).unwrap();
+            let error = segment_stats_in_file(path.to_str().unwrap(), &SafetyPolicy::default()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().contains(message));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::{self, BufRead, Write};
+
+use serde::Serialize;
+
+use crate::{Rule, SafetyPolicy, SafetyVerdict};
+
+/// Counts over every report seen so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub reports: usize,
+    pub safe: usize,
+    pub dampened_safe: usize,
+    /// Lines that could not be read as a report and were not evaluated.
+    pub invalid: usize,
+}
+
+/// The first violation in a report, as given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Reason {
+    pub index: usize,
+    pub delta: i64,
+    pub rule: Rule,
+}
+
+/// One line of the verdict feed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReportRecord {
+    pub line: usize,
+    pub levels: Vec<i32>,
+    pub safe: bool,
+    pub dampened_safe: bool,
+    pub reason: Option<Reason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub totals: Totals,
+}
+
+/// Evaluates reports one line at a time, so only the current report is held
+/// in memory. Blank lines are not reports and are passed over; a line that
+/// is not UTF-8 or has a token that is not a level yields a record carrying
+/// `error` instead.
+pub struct ReportStream<R> {
+    reader: R,
+    policy: SafetyPolicy,
+    buffer: Vec<u8>,
+    line: usize,
+    totals: Totals,
+}
+
+impl<R: BufRead> ReportStream<R> {
+    pub fn new(reader: R, policy: SafetyPolicy) -> Self {
+        ReportStream { reader, policy, buffer: Vec::new(), line: 0, totals: Totals::default() }
+    }
+
+    pub fn totals(&self) -> Totals {
+        self.totals
+    }
+
+    fn evaluate(&mut self) -> ReportRecord {
+        let parsed: Result<Vec<i32>, String> = match std::str::from_utf8(&self.buffer) {
+            Ok(text) => text
+                .split_whitespace()
+                .map(|s| s.parse::<i32>().map_err(|_| format!("invalid level {:?}", s)))
+                .collect(),
+            Err(_) => Err("line is not valid UTF-8".to_string()),
+        };
+        let levels = match parsed {
+            Ok(levels) => levels,
+            Err(error) => {
+                self.totals.invalid += 1;
+                return ReportRecord {
+                    line: self.line,
+                    levels: Vec::new(),
+                    safe: false,
+                    dampened_safe: false,
+                    reason: None,
+                    removed: None,
+                    error: Some(error),
+                    totals: self.totals,
+                };
+            }
+        };
+
+        let verdict = self.policy.verdict(&levels);
+        let safe = verdict == SafetyVerdict::Safe;
+        let dampened_safe = verdict.is_safe();
+        self.totals.reports += 1;
+        self.totals.safe += safe as usize;
+        self.totals.dampened_safe += dampened_safe as usize;
+        let (reason, removed) = match verdict {
+            SafetyVerdict::Safe => (None, None),
+            SafetyVerdict::Unsafe { index, delta, rule } => (Some(Reason { index, delta, rule }), None),
+            SafetyVerdict::Dampened { removed } => {
+                let first = match self.policy.check(&levels) {
+                    SafetyVerdict::Unsafe { index, delta, rule } => Some(Reason { index, delta, rule }),
+                    _ => None,
+                };
+                (first, Some(removed))
+            }
+        };
+        ReportRecord { line: self.line, levels, safe, dampened_safe, reason, removed, error: None, totals: self.totals }
+    }
+}
+
+impl<R: BufRead> Iterator for ReportStream<R> {
+    type Item = io::Result<ReportRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            self.line += 1;
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) if self.buffer.trim_ascii().is_empty() => continue,
+                Ok(_) => return Some(Ok(self.evaluate())),
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// Writes one JSON object per report to `writer`, flushing after each so
+/// consumers of an unbounded feed see verdicts as they are produced.
+pub fn write_verdicts_jsonl<R: BufRead, W: Write>(reader: R, mut writer: W, policy: SafetyPolicy) -> io::Result<Totals> {
+    let mut stream = ReportStream::new(reader, policy);
+    for record in stream.by_ref() {
+        serde_json::to_writer(&mut writer, &record?)?;
+        writeln!(writer)?;
+        writer.flush()?;
+    }
+    Ok(stream.totals())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn streams_example() {
+        let file = File::open("data/example_1.txt").unwrap();
+        let mut output = Vec::new();
+        let totals = write_verdicts_jsonl(io::BufReader::new(file), &mut output, SafetyPolicy::default()).unwrap();
+        assert_eq!(totals, Totals { reports: 6, safe: 2, dampened_safe: 4, invalid: 0 });
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], concat!(
+            r#"{"line":1,"levels":[7,6,4,2,1],"safe":true,"dampened_safe":true,"reason":null,"#,
+            r#""totals":{"reports":1,"safe":1,"dampened_safe":1,"invalid":0}}"#
+        ));
+        assert_eq!(lines[3], concat!(
+            r#"{"line":4,"levels":[1,3,2,4,5],"safe":false,"dampened_safe":true,"#,
+            r#""reason":{"index":2,"delta":-1,"rule":"direction_change"},"removed":[1],"#,
+            r#""totals":{"reports":4,"safe":1,"dampened_safe":2,"invalid":0}}"#
+        ));
+    }
+
+    #[test]
+    fn bad_lines_do_not_stop_the_stream() {
+        let input = "1 2 3\n\n4 x 5\n-2147483648 2147483647\n9 8 7\n";
+        let records: Vec<ReportRecord> = ReportStream::new(input.as_bytes(), SafetyPolicy::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!((records[1].line, records[1].error.as_deref()), (3, Some("invalid level \"x\"")));
+        assert_eq!(records[2].reason, Some(Reason { index: 1, delta: u32::MAX as i64, rule: Rule::DeltaTooLarge }));
+        assert_eq!(records[3].line, 5);
+        assert_eq!(records[3].totals, Totals { reports: 3, safe: 2, dampened_safe: 3, invalid: 1 });
+
+        let mut output = Vec::new();
+        let totals = write_verdicts_jsonl(&b"1 2 3\n\xff 4\n9 8 7\n"[..], &mut output, SafetyPolicy::default()).unwrap();
+        assert_eq!(totals, Totals { reports: 2, safe: 2, dampened_safe: 2, invalid: 1 });
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().nth(1).unwrap().contains(r#""line":2,"levels":[],"#));
+        assert!(output.lines().nth(1).unwrap().contains(r#""error":"line is not valid UTF-8""#));
+    }
+}
@@ -0,0 +1,167 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Bytes that can never be part of an instruction, so noise cannot complete
+/// a decoy or join two pieces into something the scanner would accept.
+const NOISE: &[u8] = b"!@#$%^&*[]{}<>?;:+-_~ /|";
+
+/// Corrupted memory together with what scanning it should give, both under
+/// the puzzle's rules, where operands have one to three digits
+/// (`Operands::Bounded`), and under the default `Operands::Lenient`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Generated {
+    pub memory: String,
+    /// Sum of every product.
+    pub sum: i64,
+    /// Sum of the products reached while enabled by `do()` and `don't()`.
+    pub sum_with_disablers: i64,
+    /// Number of valid instructions, toggles included.
+    pub instructions: usize,
+    /// Number of decoys with an operand longer than three digits, which
+    /// `Operands::Lenient` reads as instructions and `Strict` rejects.
+    pub long_operands: usize,
+    /// `sum` as `Operands::Lenient` sees it, long-operand decoys included.
+    /// This is what the default `Registry` scans and `get_sum_of_multiplies` give.
+    pub lenient_sum: i64,
+    /// `sum_with_disablers` as `Operands::Lenient` sees it.
+    pub lenient_sum_with_disablers: i64,
+}
+
+fn operand(rng: &mut StdRng) -> i32 {
+    rng.gen_range(0..1000)
+}
+
+/// Text that looks like an instruction but is not one, along with the
+/// product `Operands::Lenient` reads from it if it has a long operand.
+fn decoy(rng: &mut StdRng) -> (String, Option<i64>) {
+    let (a, b) = (operand(rng), operand(rng));
+    match rng.gen_range(0..6) {
+        0 => (format!("mul({},{}", a, b), None),
+        1 => (format!("mul ( {},{})", a, b), None),
+        2 => (format!("mul({}, {})", a, b), None),
+        3 => (format!("mul[{},{}]", a, b), None),
+        4 => ("don't".to_string(), None),
+        _ => {
+            let long = rng.gen_range(1000..10000);
+            (format!("mul({},{})", long, b), Some(i64::from(long) * i64::from(b)))
+        }
+    }
+}
+
+/// Builds `size` bytes of corrupted memory from `seed`. The same seed and
+/// size always give the same memory.
+pub fn generate(seed: u64, size: usize) -> Generated {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut generated = Generated {
+        memory: String::with_capacity(size),
+        sum: 0,
+        sum_with_disablers: 0,
+        instructions: 0,
+        long_operands: 0,
+        lenient_sum: 0,
+        lenient_sum_with_disablers: 0,
+    };
+    let mut enabled = true;
+    loop {
+        let (piece, product, long_product, toggle) = match rng.gen_range(0..100) {
+            0..=39 => {
+                let len = rng.gen_range(1..8);
+                let noise = (0..len).map(|_| NOISE[rng.gen_range(0..NOISE.len())] as char).collect();
+                (noise, None, None, None)
+            }
+            40..=64 => {
+                let (a, b) = (operand(&mut rng), operand(&mut rng));
+                (format!("mul({},{})", a, b), Some(i64::from(a) * i64::from(b)), None, None)
+            }
+            65..=84 => {
+                let (piece, long_product) = decoy(&mut rng);
+                (piece, None, long_product, None)
+            }
+            85..=91 => ("do()".to_string(), None, None, Some(true)),
+            _ => ("don't()".to_string(), None, None, Some(false)),
+        };
+        if generated.memory.len() + piece.len() > size {
+            break;
+        }
+        generated.memory.push_str(&piece);
+        generated.long_operands += long_product.is_some() as usize;
+        if let Some(product) = product {
+            generated.sum += product;
+            if enabled {
+                generated.sum_with_disablers += product;
+            }
+        }
+        if let Some(product) = product.or(long_product) {
+            generated.lenient_sum += product;
+            if enabled {
+                generated.lenient_sum_with_disablers += product;
+            }
+        }
+        if let Some(toggle) = toggle {
+            enabled = toggle;
+        }
+        generated.instructions += (product.is_some() || toggle.is_some()) as usize;
+    }
+    while generated.memory.len() < size {
+        generated.memory.push(NOISE[rng.gen_range(0..NOISE.len())] as char);
+    }
+    generated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::{evaluate_checked, evaluate_with_disablers_checked, parse_strict, NewlinePolicy, Operands, Registry, ScanError};
+    use crate::{
+        get_sum_of_multiplies, get_sum_of_multiplies_strict, get_sum_of_multiplies_with_disablers,
+        get_sum_of_multiplies_with_disablers_strict, get_sum_with_registry,
+    };
+
+    #[test]
+    fn answers_match_the_scanner() {
+        for seed in 0..20 {
+            let generated = generate(seed, 5_000);
+            assert_eq!(generated.memory.len(), 5_000);
+            assert_eq!(generate(seed, 5_000), generated);
+
+            let parsed: Vec<_> = Parser::new(&generated.memory).with_operands(Operands::Bounded).collect();
+            assert_eq!(parsed.len(), generated.instructions);
+            assert_eq!(evaluate_checked(&parsed).unwrap(), generated.sum);
+            assert_eq!(evaluate_with_disablers_checked(&parsed).unwrap(), generated.sum_with_disablers);
+
+            let streamed = Registry::standard().chunked(generated.memory.as_bytes(), NewlinePolicy::Split, 5)
+                .with_operands(Operands::Bounded)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>();
+            assert_eq!(streamed, parsed);
+
+            let strict = parse_strict(&generated.memory);
+            assert_eq!(matches!(strict, Err(ScanError::InvalidOperand { .. })), generated.long_operands > 0);
+
+            assert_eq!(Registry::multiplies().scan(&generated.memory).unwrap().sum, generated.lenient_sum);
+            assert_eq!(Registry::standard().scan(&generated.memory).unwrap().sum, generated.lenient_sum_with_disablers);
+        }
+    }
+
+    #[test]
+    fn answers_match_the_file_entry_points() {
+        let path = std::env::temp_dir().join(format!("day_3_generated_{}.txt", std::process::id()));
+        for seed in 0..10 {
+            let generated = generate(seed, 5_000);
+            std::fs::write(&path, &generated.memory).unwrap();
+            let path = path.to_str().unwrap();
+            assert_eq!(i64::from(get_sum_of_multiplies(path)), generated.lenient_sum);
+            assert_eq!(i64::from(get_sum_of_multiplies_with_disablers(path)), generated.lenient_sum_with_disablers);
+            assert_eq!(get_sum_with_registry(path, &Registry::multiplies()).unwrap(), generated.lenient_sum);
+            if generated.long_operands == 0 {
+                assert_eq!(get_sum_of_multiplies_strict(path).unwrap(), generated.sum);
+                assert_eq!(get_sum_of_multiplies_with_disablers_strict(path).unwrap(), generated.sum_with_disablers);
+            } else {
+                assert!(matches!(get_sum_of_multiplies_strict(path), Err(ScanError::InvalidOperand { .. })));
+                assert!(matches!(get_sum_of_multiplies_with_disablers_strict(path), Err(ScanError::InvalidOperand { .. })));
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,53 +1,130 @@
-
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead};
-use regex::Regex;
+use std::io::{self, Write};
 
+pub mod generator;
+pub mod parser;
+pub mod registry;
+pub mod stream;
+pub mod trace;
 
-fn read_input_from_file(file_path: &str) -> io::Result<String> {
-    // Open the file
-    let file = File::open(file_path)?;
-    let reader = io::BufReader::new(file);
+pub use generator::{generate, Generated};
+pub use parser::{parse, parse_strict, Instruction, Operands, Spanned};
+pub use registry::{Handler, InvalidName, Registry, State};
+pub use stream::{ChunkedScanner, NewlinePolicy};
+pub use trace::{render, Highlight, TraceEntry};
 
-    // Create the output vector
-    let output: String = reader.lines().map(|line| {
-        line.expect("")
-    }).collect();
-    Ok(output)
+/// Why a scan or a checked evaluation stopped. Offsets are the byte at
+/// which the offending instruction starts.
+#[derive(Debug)]
+pub enum ScanError {
+    Io(io::Error),
+    /// An instruction with an operand of more than three digits, in strict mode.
+    InvalidOperand { offset: usize },
+    /// The product or the running sum no longer fits an `i64`.
+    Overflow { offset: usize },
 }
 
-pub fn get_sum_of_multiplies(file_path: &str) -> i32 {
-    let re = Regex::new(r"mul\([0-9]+,[0-9]+\)").unwrap();
-    let input = read_input_from_file(file_path).expect("");
-    re.find_iter(input.as_str()).map(|capture| {
-        let re = Regex::new(r"mul\((\d+),(\d+)\)").unwrap();
-        let two_nums = re.captures(capture.as_str()).unwrap();
-        let num_1: i32 = two_nums.get(1).unwrap().as_str().parse().unwrap();
-        let num_2: i32 = two_nums.get(2).unwrap().as_str().parse().unwrap();
-        num_1 * num_2
-    }).sum()
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::Io(error) => write!(f, "read failed: {}", error),
+            ScanError::InvalidOperand { offset } => write!(f, "byte {}: operand longer than three digits", offset),
+            ScanError::Overflow { offset } => write!(f, "byte {}: sum overflows", offset),
+        }
+    }
 }
 
-pub fn get_sum_of_multiplies_with_disablers(file_path: &str) -> i32 {
-    let re = Regex::new(
-        r"mul\([0-9]+,[0-9]+\)|do\(\)|don't\(\)").unwrap();
-    let digits_re = Regex::new(r"mul\((\d+),(\d+)\)").unwrap();
-    let input = read_input_from_file(file_path).expect("");
-    let mut enable: bool = true;
-    let mut sum_all = 0;
-    re.find_iter(input.as_str()).for_each(|expression| {
-        match expression.as_str() {
-            "do()" => enable = true,
-            "don't()" => enable = false,
-            _ => if enable {
-                let two_nums = digits_re.captures(expression.as_str()).unwrap();
-                let num_1: i32 = two_nums.get(1).unwrap().as_str().parse().unwrap();
-                let num_2: i32 = two_nums.get(2).unwrap().as_str().parse().unwrap();
-                sum_all += num_1 * num_2;
+impl Error for ScanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScanError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Sums products in `i64`, failing instead of wrapping or panicking.
+/// With `disablers`, `do()` and `don't()` switch multiplication on and off.
+fn checked_sum<I>(instructions: I, disablers: bool) -> Result<i64, ScanError>
+where
+    I: IntoIterator<Item = Result<Spanned, ScanError>>,
+{
+    let (mut sum, mut enabled) = (0i64, true);
+    for spanned in instructions {
+        let spanned = spanned?;
+        match spanned.instruction {
+            Instruction::Mul(a, b) if enabled => {
+                sum = i64::from(a).checked_mul(i64::from(b))
+                    .and_then(|product| sum.checked_add(product))
+                    .ok_or(ScanError::Overflow { offset: spanned.span.start })?;
             }
+            Instruction::Do if disablers => enabled = true,
+            Instruction::Dont if disablers => enabled = false,
+            _ => {}
         }
-    });
-    sum_all
+    }
+    Ok(sum)
+}
+
+pub fn evaluate_checked(instructions: &[Spanned]) -> Result<i64, ScanError> {
+    checked_sum(instructions.iter().cloned().map(Ok), false)
+}
+
+pub fn evaluate_with_disablers_checked(instructions: &[Spanned]) -> Result<i64, ScanError> {
+    checked_sum(instructions.iter().cloned().map(Ok), true)
+}
+
+/// Streams the file under `Operands::Strict` with checked arithmetic.
+fn strict_sum_in_file(file_path: &str, disablers: bool) -> Result<i64, ScanError> {
+    let file = File::open(file_path).map_err(ScanError::Io)?;
+    let registry = if disablers { Registry::standard() } else { Registry::multiplies() };
+    let scanner = registry.chunked(file, NewlinePolicy::Join, stream::DEFAULT_CHUNK_SIZE).with_operands(Operands::Strict);
+    checked_sum(scanner, disablers)
+}
+
+pub fn get_sum_of_multiplies_strict(file_path: &str) -> Result<i64, ScanError> {
+    strict_sum_in_file(file_path, false)
+}
+
+pub fn get_sum_of_multiplies_with_disablers_strict(file_path: &str) -> Result<i64, ScanError> {
+    strict_sum_in_file(file_path, true)
+}
+
+/// Panics if the file cannot be read or the sum does not fit an `i32`;
+/// `get_sum_with_registry` reports both instead.
+pub fn get_sum_of_multiplies(file_path: &str) -> i32 {
+    narrow(get_sum_with_registry(file_path, &Registry::multiplies()))
+}
+
+/// Panics like `get_sum_of_multiplies`.
+pub fn get_sum_of_multiplies_with_disablers(file_path: &str) -> i32 {
+    narrow(get_sum_with_registry(file_path, &Registry::standard()))
+}
+
+fn narrow(sum: Result<i64, ScanError>) -> i32 {
+    i32::try_from(sum.expect("")).expect("")
+}
+
+/// Like `get_sum_of_multiplies_with_disablers`, also writing a line per
+/// instruction to `trace` (see `TraceEntry`).
+pub fn get_sum_of_multiplies_with_disablers_traced(file_path: &str, trace: &mut impl Write) -> Result<i64, ScanError> {
+    let registry = Registry::standard();
+    let file = File::open(file_path).map_err(ScanError::Io)?;
+    let mut state = State::default();
+    for spanned in registry.chunked(file, NewlinePolicy::Join, stream::DEFAULT_CHUNK_SIZE) {
+        let entry = registry.step(&mut state, spanned?)?;
+        writeln!(trace, "{}", entry).map_err(ScanError::Io)?;
+    }
+    Ok(state.sum)
+}
+
+/// Scans the file for every instruction `registry` knows and returns the sum.
+/// Lines are joined, so an instruction may continue on the next line.
+pub fn get_sum_with_registry(file_path: &str, registry: &Registry) -> Result<i64, ScanError> {
+    let file = File::open(file_path).map_err(ScanError::Io)?;
+    Ok(registry.scan_reader(file, NewlinePolicy::Join, stream::DEFAULT_CHUNK_SIZE)?.sum)
 }
 
 #[cfg(test)]
@@ -59,4 +136,51 @@ mod tests {
         assert_eq!(get_sum_of_multiplies("data/input_1.txt"), 173731097);
         assert_eq!(get_sum_of_multiplies_with_disablers("data/input_1.txt"), 93729253);
     }
+
+    #[test]
+    fn strict_mode() {
+        assert_eq!(get_sum_of_multiplies_strict("data/input_1.txt").unwrap(), 173731097);
+        assert_eq!(get_sum_of_multiplies_with_disablers_strict("data/input_1.txt").unwrap(), 93729253);
+
+        let big = format!("mul({},{})mul(2,2)", i32::MAX, i32::MAX);
+        let instructions = parse(&big);
+        assert_eq!(evaluate_checked(&instructions).unwrap(), i64::from(i32::MAX).pow(2) + 4);
+        let twice = parse(&big.repeat(3));
+        assert!(matches!(evaluate_checked(&twice), Err(ScanError::Overflow { offset: 68 })));
+        assert!(matches!(parse_strict(&big), Err(ScanError::InvalidOperand { offset: 0 })));
+    }
+
+    #[test]
+    fn file_errors_point_into_the_file() {
+        let path = std::env::temp_dir().join(format!("day_3_offsets_{}.txt", std::process::id()));
+        let big = format!("mul({},{})", i32::MAX, i32::MAX);
+        std::fs::write(&path, format!("mul(1,2)\r\n{}\n{}\n{}", big, big, big)).unwrap();
+        let path = path.to_str().unwrap();
+        let strict = get_sum_of_multiplies_strict(path);
+        let traced = get_sum_of_multiplies_with_disablers_traced(path, &mut Vec::new());
+        let lenient = get_sum_with_registry(path, &Registry::multiplies());
+        std::fs::remove_file(path).unwrap();
+        // The line breaks before the third `mul` take up three bytes.
+        assert!(matches!(strict, Err(ScanError::InvalidOperand { offset: 10 })));
+        assert!(matches!(traced, Err(ScanError::Overflow { offset: 64 })));
+        assert!(matches!(lenient, Err(ScanError::Overflow { offset: 64 })));
+    }
+
+    #[test]
+    fn traced_sum() {
+        let mut trace = Vec::new();
+        let sum = get_sum_of_multiplies_with_disablers_traced("data/input_1.txt", &mut trace).unwrap();
+        assert_eq!(sum, 93729253);
+        let joined = std::fs::read_to_string("data/input_1.txt").unwrap().replace('\n', "");
+        assert_eq!(String::from_utf8(trace).unwrap().lines().count(), parse(&joined).len());
+    }
+
+    #[test]
+    fn chunk_size_does_not_change_the_answer() {
+        let input = std::fs::read("data/input_1.txt").unwrap();
+        for chunk_size in [1, 3, 7, 100] {
+            let state = Registry::standard().scan_reader(input.as_slice(), NewlinePolicy::Join, chunk_size).unwrap();
+            assert_eq!(state.sum, 93729253);
+        }
+    }
 }
@@ -0,0 +1,263 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::ScanError;
+
+/// An instruction recognised in corrupted memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Mul(i32, i32),
+    Do,
+    Dont,
+    /// Any other instruction a `Registry` taught the parser.
+    Custom { name: String, args: Vec<i32> },
+}
+
+impl Instruction {
+    fn new(name: &str, args: Vec<i32>) -> Self {
+        match (name, args.as_slice()) {
+            ("mul", &[a, b]) => Instruction::Mul(a, b),
+            ("do", []) => Instruction::Do,
+            ("don't", []) => Instruction::Dont,
+            _ => Instruction::Custom { name: name.to_string(), args },
+        }
+    }
+
+    /// Calls `f` with the instruction's name and operands.
+    pub fn with_call<T>(&self, f: impl FnOnce(&str, &[i32]) -> T) -> T {
+        match self {
+            Instruction::Mul(a, b) => f("mul", &[*a, *b]),
+            Instruction::Do => f("do", &[]),
+            Instruction::Dont => f("don't", &[]),
+            Instruction::Custom { name, args } => f(name, args),
+        }
+    }
+}
+
+/// Writes the instruction as it would appear in memory, e.g. `mul(2,4)`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_call(|name, args| {
+            let args: Vec<String> = args.iter().map(i32::to_string).collect();
+            write!(f, "{}({})", name, args.join(","))
+        })
+    }
+}
+
+/// Names and arities of the instructions the puzzle defines.
+pub const BUILTIN_SIGNATURES: [(&str, usize); 3] = [("mul", 2), ("do", 0), ("don't", 0)];
+
+/// An instruction together with the bytes of the input it was read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Spanned {
+    pub instruction: Instruction,
+    pub span: Range<usize>,
+}
+
+/// How operands are read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Operands {
+    /// Any run of digits that fits an `i32`; longer runs are not operands.
+    #[default]
+    Lenient,
+    /// One to three digits, as the puzzle specifies; longer runs are not
+    /// operands.
+    Bounded,
+    /// One to three digits, but an instruction that is well-formed apart
+    /// from a longer operand is an error, not noise.
+    Strict,
+}
+
+/// Most digits an operand may have under `Operands::Bounded` and `Strict`.
+pub const MAX_OPERAND_DIGITS: usize = 3;
+
+/// Single-pass scanner over corrupted memory. Like a leftmost-first regex
+/// search, it tries to read an instruction at each byte and, on success,
+/// resumes right after it; anything else is skipped one byte at a time.
+///
+/// An instruction is a known name, `(`, exactly as many comma-separated
+/// numbers as its arity, and `)`. Names are tried in the order given.
+pub struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    signatures: Vec<(&'a str, usize)>,
+    operands: Operands,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_signatures(input, BUILTIN_SIGNATURES.to_vec())
+    }
+
+    pub fn with_signatures(input: &'a str, signatures: Vec<(&'a str, usize)>) -> Self {
+        Parser { input: input.as_bytes(), pos: 0, signatures, operands: Operands::Lenient }
+    }
+
+    /// Strict operands are read as bounded ones, since the parser has no
+    /// way to report an error; use `parse_strict` for that.
+    pub fn with_operands(mut self, operands: Operands) -> Self {
+        self.operands = match operands {
+            Operands::Strict => Operands::Bounded,
+            operands => operands,
+        };
+        self
+    }
+}
+
+/// Why no instruction was read at a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Miss {
+    NoMatch,
+    /// The input ends before it is clear whether an instruction starts here.
+    NeedMore,
+    /// An instruction starts here, but an operand breaks `Operands::Strict`.
+    Invalid,
+}
+
+/// Reads instructions out of `input`. Unless `complete`, more input may
+/// follow, so a match that runs into the end is `NeedMore` rather than
+/// `NoMatch`.
+pub(crate) struct Matcher<'a> {
+    pub input: &'a [u8],
+    pub complete: bool,
+    pub operands: Operands,
+}
+
+impl Matcher<'_> {
+    fn literal(&self, at: usize, text: &str) -> Result<usize, Miss> {
+        let rest = &self.input[at..];
+        if rest.starts_with(text.as_bytes()) {
+            Ok(at + text.len())
+        } else if !self.complete && text.as_bytes().starts_with(rest) {
+            Err(Miss::NeedMore)
+        } else {
+            Err(Miss::NoMatch)
+        }
+    }
+
+    /// Reads one or more digits as an `i32`. A run that is too long for
+    /// the operand rule gives `None` in strict mode and no match otherwise.
+    fn number(&self, at: usize) -> Result<(Option<i32>, usize), Miss> {
+        let digits = self.input[at..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if !self.complete && at + digits == self.input.len() {
+            return Err(Miss::NeedMore);
+        }
+        if digits == 0 {
+            return Err(Miss::NoMatch);
+        }
+        let end = at + digits;
+        let value = std::str::from_utf8(&self.input[at..end]).ok().and_then(|text| text.parse().ok());
+        match self.operands {
+            Operands::Strict if digits > MAX_OPERAND_DIGITS => Ok((None, end)),
+            Operands::Bounded if digits > MAX_OPERAND_DIGITS => Err(Miss::NoMatch),
+            Operands::Lenient if value.is_none() => Err(Miss::NoMatch),
+            _ => Ok((value, end)),
+        }
+    }
+
+    fn signature_at(&self, at: usize, name: &str, arity: usize) -> Result<(Instruction, usize), Miss> {
+        let mut end = self.literal(self.literal(at, name)?, "(")?;
+        let mut args = Vec::with_capacity(arity);
+        for i in 0..arity {
+            if i > 0 {
+                end = self.literal(end, ",")?;
+            }
+            let (arg, next) = self.number(end)?;
+            args.push(arg);
+            end = next;
+        }
+        end = self.literal(end, ")")?;
+        match args.into_iter().collect::<Option<Vec<i32>>>() {
+            Some(args) => Ok((Instruction::new(name, args), end)),
+            None => Err(Miss::Invalid),
+        }
+    }
+
+    /// `name(args)` for the first of `signatures` that matches exactly at `at`.
+    /// A signature that might still match once more input arrives takes
+    /// precedence over those after it, so the answer is then `NeedMore`.
+    pub fn instruction_at(&self, at: usize, signatures: &[(&str, usize)]) -> Result<(Instruction, usize), Miss> {
+        for &(name, arity) in signatures {
+            match self.signature_at(at, name, arity) {
+                Err(Miss::NoMatch) => continue,
+                result => return result,
+            }
+        }
+        Err(Miss::NoMatch)
+    }
+}
+
+impl Iterator for Parser<'_> {
+    type Item = Spanned;
+
+    fn next(&mut self) -> Option<Spanned> {
+        let matcher = Matcher { input: self.input, complete: true, operands: self.operands };
+        while self.pos < self.input.len() {
+            let start = self.pos;
+            match matcher.instruction_at(start, &self.signatures) {
+                Ok((instruction, end)) => {
+                    self.pos = end;
+                    return Some(Spanned { instruction, span: start..end });
+                }
+                Err(_) => self.pos += 1,
+            }
+        }
+        None
+    }
+}
+
+pub fn parse(input: &str) -> Vec<Spanned> {
+    Parser::new(input).collect()
+}
+
+/// Like `parse`, under `Operands::Strict`. Fails at the first instruction
+/// with an operand of more than three digits.
+pub fn parse_strict(input: &str) -> Result<Vec<Spanned>, ScanError> {
+    let matcher = Matcher { input: input.as_bytes(), complete: true, operands: Operands::Strict };
+    let mut parsed = Vec::new();
+    let mut pos = 0;
+    while pos < input.len() {
+        match matcher.instruction_at(pos, &BUILTIN_SIGNATURES) {
+            Ok((instruction, end)) => {
+                parsed.push(Spanned { instruction, span: pos..end });
+                pos = end;
+            }
+            Err(Miss::Invalid) => return Err(ScanError::InvalidOperand { offset: pos }),
+            Err(_) => pos += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_example() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let parsed = parse(input);
+        let instructions: Vec<Instruction> = parsed.iter().map(|s| s.instruction.clone()).collect();
+        assert_eq!(instructions, vec![
+            Instruction::Mul(2, 4),
+            Instruction::Dont,
+            Instruction::Mul(5, 5),
+            Instruction::Mul(11, 8),
+            Instruction::Do,
+            Instruction::Mul(8, 5),
+        ]);
+        assert_eq!(parsed[0].span, 1..9);
+        assert_eq!(&input[parsed[1].span.clone()], "don't()");
+        assert_eq!(parse("mul(mul(1,2)mul(99999999999,1)"), vec![
+            Spanned { instruction: Instruction::Mul(1, 2), span: 4..12 },
+        ]);
+    }
+
+    #[test]
+    fn strict_operands() {
+        assert_eq!(parse("mul(1000,2)").len(), 1);
+        assert_eq!(parse_strict("mul(999,2)mul(1000,2").unwrap().len(), 1);
+        assert!(matches!(parse_strict("xmul(2,3)_mul(1000,2)"), Err(ScanError::InvalidOperand { offset: 10 })));
+        assert!(matches!(parse_strict("do()mul(99999999999,1)"), Err(ScanError::InvalidOperand { offset: 4 })));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+
+use crate::parser::{Parser, Spanned, BUILTIN_SIGNATURES};
+use crate::stream::{ChunkedScanner, NewlinePolicy};
+use crate::ScanError;
+
+/// What the scanner carries from one instruction to the next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct State {
+    pub enabled: bool,
+    pub sum: i64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State { enabled: true, sum: 0 }
+    }
+}
+
+pub type ValueFn = Box<dyn Fn(&[i32]) -> i64>;
+pub type TransitionFn = Box<dyn Fn(&mut State, &[i32])>;
+
+pub enum Handler {
+    /// Computes a value that is added to the sum while the scanner is enabled.
+    Value(ValueFn),
+    /// Changes the state directly, whether the scanner is enabled or not.
+    Transition(TransitionFn),
+}
+
+/// An instruction name that is empty or contains `(`, so it could never be
+/// told apart from its operand list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidName {
+    pub name: String,
+}
+
+impl fmt::Display for InvalidName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid instruction name {:?}", self.name)
+    }
+}
+
+impl Error for InvalidName {}
+
+struct Entry {
+    name: String,
+    arity: usize,
+    handler: Handler,
+}
+
+/// The instructions a scan recognises and what each of them does.
+/// Names are tried in registration order when parsing.
+#[derive(Default)]
+pub struct Registry {
+    entries: Vec<Entry>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only `mul`, as in the first part of the puzzle.
+    pub fn multiplies() -> Self {
+        let mut registry = Self::new();
+        registry.register_value(BUILTIN_SIGNATURES[0].0, 2, |args| i64::from(args[0]) * i64::from(args[1]))
+            .expect("built-in names are valid");
+        registry
+    }
+
+    /// `mul`, `do` and `don't` with their puzzle meaning.
+    pub fn standard() -> Self {
+        let mut registry = Self::multiplies();
+        let [_, (enable, _), (disable, _)] = BUILTIN_SIGNATURES;
+        registry.register_transition(enable, 0, |state, _| state.enabled = true).expect("built-in names are valid");
+        registry.register_transition(disable, 0, |state, _| state.enabled = false).expect("built-in names are valid");
+        registry
+    }
+
+    /// Adds an instruction, or replaces the one already registered under
+    /// `name` while keeping its place in the parsing order. Fails if `name`
+    /// is empty or contains `(`.
+    pub fn register(&mut self, name: &str, arity: usize, handler: Handler) -> Result<&mut Self, InvalidName> {
+        if name.is_empty() || name.contains('(') {
+            return Err(InvalidName { name: name.to_string() });
+        }
+        let entry = Entry { name: name.to_string(), arity, handler };
+        match self.by_name.get(name) {
+            Some(&index) => self.entries[index] = entry,
+            None => {
+                self.by_name.insert(name.to_string(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn register_value(&mut self, name: &str, arity: usize, f: impl Fn(&[i32]) -> i64 + 'static) -> Result<&mut Self, InvalidName> {
+        self.register(name, arity, Handler::Value(Box::new(f)))
+    }
+
+    pub fn register_transition(&mut self, name: &str, arity: usize, f: impl Fn(&mut State, &[i32]) + 'static) -> Result<&mut Self, InvalidName> {
+        self.register(name, arity, Handler::Transition(Box::new(f)))
+    }
+
+    fn signatures(&self) -> Vec<(&str, usize)> {
+        self.entries.iter().map(|e| (e.name.as_str(), e.arity)).collect()
+    }
+
+    pub fn parser<'a>(&'a self, input: &'a str) -> Parser<'a> {
+        Parser::with_signatures(input, self.signatures())
+    }
+
+    /// A scanner that reads `reader` `chunk_size` bytes at a time.
+    pub fn chunked<R: Read>(&self, reader: R, newlines: NewlinePolicy, chunk_size: usize) -> ChunkedScanner<'_, R> {
+        ChunkedScanner::new(reader, self.signatures(), newlines, chunk_size)
+    }
+
+    /// Applies one instruction to `state` and tells whether it had any
+    /// effect: instructions this registry does not know are ignored, and
+    /// values are only added while enabled. Fails, leaving `state` as it
+    /// was, if the sum would no longer fit an `i64`.
+    pub fn apply(&self, state: &mut State, spanned: &Spanned) -> Result<bool, ScanError> {
+        spanned.instruction.with_call(|name, args| {
+            let Some(&index) = self.by_name.get(name) else { return Ok(false) };
+            match &self.entries[index].handler {
+                Handler::Value(f) => {
+                    if state.enabled {
+                        state.sum = state.sum.checked_add(f(args))
+                            .ok_or(ScanError::Overflow { offset: spanned.span.start })?;
+                    }
+                    Ok(state.enabled)
+                }
+                Handler::Transition(f) => {
+                    f(state, args);
+                    Ok(true)
+                }
+            }
+        })
+    }
+
+    pub fn evaluate(&self, instructions: &[Spanned]) -> Result<State, ScanError> {
+        let mut state = State::default();
+        for spanned in instructions {
+            self.apply(&mut state, spanned)?;
+        }
+        Ok(state)
+    }
+
+    pub fn scan(&self, input: &str) -> Result<State, ScanError> {
+        let mut state = State::default();
+        for spanned in self.parser(input) {
+            self.apply(&mut state, &spanned)?;
+        }
+        Ok(state)
+    }
+
+    /// Like `scan`, without holding more than a chunk of `reader` in memory.
+    pub fn scan_reader<R: Read>(&self, reader: R, newlines: NewlinePolicy, chunk_size: usize) -> Result<State, ScanError> {
+        let mut state = State::default();
+        for spanned in self.chunked(reader, newlines, chunk_size) {
+            self.apply(&mut state, &spanned?)?;
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_instructions() {
+        let mut registry = Registry::standard();
+        registry
+            .register_value("add", 2, |args| i64::from(args[0]) + i64::from(args[1])).unwrap()
+            .register_value("sub", 2, |args| i64::from(args[0]) - i64::from(args[1])).unwrap()
+            .register_value("neg", 1, |args| -i64::from(args[0])).unwrap()
+            .register_transition("reset", 0, |state, _| state.sum = 0).unwrap()
+            .register_transition("toggle", 0, |state, _| state.enabled = !state.enabled).unwrap();
+
+        let input = "mul(2,3)add(1,1)add(1)sub(1,5)reset()neg(7)toggle()mul(9,9)toggle()do()add(10,0)";
+        assert_eq!(registry.scan(input).unwrap(), State { enabled: true, sum: -7 + 10 });
+        // The standard registry skips what it does not know.
+        assert_eq!(Registry::standard().scan(input).unwrap(), State { enabled: true, sum: 6 + 81 });
+    }
+
+    #[test]
+    fn replacing_an_instruction() {
+        let mut registry = Registry::standard();
+        registry.register_value("mul", 2, |args| i64::from(args[0]) + i64::from(args[1])).unwrap();
+        assert_eq!(registry.scan("mul(2,3)don't()mul(4,4)").unwrap().sum, 5);
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut registry = Registry::standard();
+        assert_eq!(registry.register_value("", 0, |_| 1).err(), Some(InvalidName { name: String::new() }));
+        assert_eq!(registry.register_transition("f(", 0, |_, _| {}).err(), Some(InvalidName { name: "f(".to_string() }));
+        assert_eq!(registry.scan("mul(2,3)").unwrap().sum, 6);
+    }
+
+    #[test]
+    fn large_operands() {
+        assert_eq!(Registry::standard().scan("mul(100000,100000)").unwrap().sum, 10_000_000_000);
+        let big = format!("mul({},{})", i32::MAX, i32::MAX).repeat(3);
+        assert!(matches!(Registry::multiplies().scan(&big), Err(ScanError::Overflow { offset: 52 })));
+        let disabled = format!("{}don't(){}", &big[..52], &big[52..]);
+        assert_eq!(Registry::standard().scan(&disabled).unwrap().sum, 2 * i64::from(i32::MAX).pow(2));
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::{self, Read};
+
+use crate::parser::{Matcher, Miss, Operands, Spanned};
+use crate::ScanError;
+
+/// Default number of bytes read from the underlying reader at a time.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// What a line break in the input means to the scanner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NewlinePolicy {
+    /// Line breaks (`\n` or `\r\n`) are dropped before scanning, so an
+    /// instruction may continue on the next line. This is how the puzzle
+    /// input has always been read. Spans and error offsets still refer to
+    /// the raw input, so a joined instruction's span covers its line break.
+    Join,
+    /// Line breaks are corrupted bytes like any other, so no instruction
+    /// can span two lines. Spans refer to the raw input.
+    Split,
+}
+
+/// Scans any reader for instructions in fixed-size chunks, holding on to
+/// the unfinished tail of a chunk only while it could still be the start
+/// of an instruction. Memory use is one chunk plus the longest partial
+/// instruction, however long the input is.
+pub struct ChunkedScanner<'a, R> {
+    reader: R,
+    signatures: Vec<(&'a str, usize)>,
+    newlines: NewlinePolicy,
+    operands: Operands,
+    chunk: Vec<u8>,
+    /// Text read but not dropped yet; `pending[0]` is at `offset` once
+    /// line breaks are taken out.
+    pending: Vec<u8>,
+    offset: usize,
+    /// Line breaks taken out of `pending`, as the index of the byte they
+    /// came before and the number of bytes taken out of the input up to
+    /// and including them.
+    breaks: Vec<(usize, usize)>,
+    /// Bytes taken out of the input before `pending[0]`.
+    dropped: usize,
+    /// Everything in `pending` before this has been scanned. It is only
+    /// dropped when the next chunk is read, so a match costs no copying.
+    start: usize,
+    /// A trailing `\r` held back until we know whether `\n` follows it.
+    carriage_return: bool,
+    eof: bool,
+}
+
+impl<'a, R: Read> ChunkedScanner<'a, R> {
+    pub fn new(reader: R, signatures: Vec<(&'a str, usize)>, newlines: NewlinePolicy, chunk_size: usize) -> Self {
+        ChunkedScanner {
+            reader,
+            signatures,
+            newlines,
+            operands: Operands::Lenient,
+            chunk: vec![0; chunk_size.max(1)],
+            pending: Vec::new(),
+            offset: 0,
+            breaks: Vec::new(),
+            dropped: 0,
+            start: 0,
+            carriage_return: false,
+            eof: false,
+        }
+    }
+
+    pub fn with_operands(mut self, operands: Operands) -> Self {
+        self.operands = operands;
+        self
+    }
+
+    /// Drops the scanned text and appends the next chunk to `pending`,
+    /// applying the newline policy.
+    fn fill(&mut self) -> io::Result<()> {
+        self.pending.drain(..self.start);
+        self.offset += self.start;
+        let passed = self.breaks.partition_point(|&(index, _)| index <= self.start);
+        if passed > 0 {
+            self.dropped = self.breaks[passed - 1].1;
+        }
+        self.breaks.drain(..passed);
+        self.breaks.iter_mut().for_each(|(index, _)| *index -= self.start);
+        self.start = 0;
+        let read = loop {
+            match self.reader.read(&mut self.chunk) {
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        if read == 0 {
+            self.eof = true;
+            if self.carriage_return {
+                self.pending.push(b'\r');
+                self.carriage_return = false;
+            }
+            return Ok(());
+        }
+        let chunk = &self.chunk[..read];
+        match self.newlines {
+            NewlinePolicy::Split => self.pending.extend_from_slice(chunk),
+            NewlinePolicy::Join => {
+                let (breaks, dropped) = (&mut self.breaks, self.dropped);
+                let mut take_out = |index: usize, bytes: usize| match breaks.last_mut() {
+                    Some(last) if last.0 == index => last.1 += bytes,
+                    last => {
+                        let before = last.map_or(dropped, |&mut (_, total)| total);
+                        breaks.push((index, before + bytes));
+                    }
+                };
+                for (i, &byte) in chunk.iter().enumerate() {
+                    let held = std::mem::take(&mut self.carriage_return);
+                    if held && byte != b'\n' {
+                        self.pending.push(b'\r');
+                    }
+                    match byte {
+                        b'\n' => take_out(self.pending.len(), 1 + held as usize),
+                        b'\r' if i + 1 == chunk.len() => self.carriage_return = true,
+                        b'\r' if chunk[i + 1] == b'\n' => take_out(self.pending.len(), 1),
+                        _ => self.pending.push(byte),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Where `pending[index]` is in the raw input.
+    fn raw_offset(&self, index: usize) -> usize {
+        let before = self.breaks.partition_point(|&(at, _)| at <= index);
+        let dropped = before.checked_sub(1).map_or(self.dropped, |last| self.breaks[last].1);
+        self.offset + index + dropped
+    }
+}
+
+impl<R: Read> Iterator for ChunkedScanner<'_, R> {
+    type Item = Result<Spanned, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let matcher = Matcher { input: &self.pending, complete: self.eof, operands: self.operands };
+            let mut pos = self.start;
+            while pos < self.pending.len() {
+                match matcher.instruction_at(pos, &self.signatures) {
+                    Ok((instruction, end)) => {
+                        self.start = end;
+                        let span = self.raw_offset(pos)..self.raw_offset(end - 1) + 1;
+                        return Some(Ok(Spanned { instruction, span }));
+                    }
+                    Err(Miss::NeedMore) => break,
+                    Err(Miss::NoMatch) => pos += 1,
+                    Err(Miss::Invalid) => {
+                        // Step past it so the scan can go on if the caller wants.
+                        self.start = pos + 1;
+                        return Some(Err(ScanError::InvalidOperand { offset: self.raw_offset(pos) }));
+                    }
+                }
+            }
+            // Everything before `pos` can no longer start an instruction.
+            self.start = pos;
+            if self.eof {
+                return None;
+            }
+            if let Err(error) = self.fill() {
+                return Some(Err(ScanError::Io(error)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, BUILTIN_SIGNATURES};
+    use crate::{Instruction, Operands};
+
+    fn scan(input: &str, newlines: NewlinePolicy, chunk_size: usize) -> Vec<Spanned> {
+        ChunkedScanner::new(input.as_bytes(), BUILTIN_SIGNATURES.to_vec(), newlines, chunk_size)
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn any_chunk_size_gives_the_same_instructions() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))mul(123,4";
+        let expected = parse(input);
+        for chunk_size in 1..=input.len() + 1 {
+            assert_eq!(scan(input, NewlinePolicy::Split, chunk_size), expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn newline_policy() {
+        let input = "mul(1\n2,3)do\r\n()x\rmul(4,5)";
+        for chunk_size in [1, 2, 3, 7, 64] {
+            let joined = scan(input, NewlinePolicy::Join, chunk_size);
+            assert_eq!(joined, vec![
+                Spanned { instruction: Instruction::Mul(12, 3), span: 0..10 },
+                Spanned { instruction: Instruction::Do, span: 10..16 },
+                Spanned { instruction: Instruction::Mul(4, 5), span: 18..26 },
+            ]);
+            let split = scan(input, NewlinePolicy::Split, chunk_size);
+            assert_eq!(split, vec![Spanned { instruction: Instruction::Mul(4, 5), span: 18..26 }]);
+        }
+    }
+
+    #[test]
+    fn strict_errors_across_chunks() {
+        for chunk_size in [1, 4, 64] {
+            let input = "mul(1,2)\nmul(12\n34,5)mul(3,4)".as_bytes();
+            let mut scanner = ChunkedScanner::new(input, BUILTIN_SIGNATURES.to_vec(), NewlinePolicy::Join, chunk_size)
+                .with_operands(Operands::Strict);
+            assert_eq!(scanner.next().unwrap().unwrap().instruction, Instruction::Mul(1, 2));
+            assert!(matches!(scanner.next(), Some(Err(ScanError::InvalidOperand { offset: 9 }))));
+            assert_eq!(scanner.next().unwrap().unwrap().instruction, Instruction::Mul(3, 4));
+            assert!(scanner.next().is_none());
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::{Instruction, Registry, ScanError, Spanned, State};
+
+/// What one instruction did during a scan.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub instruction: Instruction,
+    pub span: Range<usize>,
+    /// Whether multiplication was enabled when the instruction was reached.
+    pub enabled: bool,
+    /// Whether the instruction took effect; a value read while disabled does not.
+    pub applied: bool,
+    /// How much the instruction added to the sum.
+    pub contribution: i64,
+}
+
+/// One line per entry: offset, instruction, state and contribution.
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.enabled { "on" } else { "off" };
+        write!(f, "{:>8}  {:<16} {:<3} {:+}", self.span.start, self.instruction.to_string(), state, self.contribution)
+    }
+}
+
+impl Registry {
+    /// Applies `spanned` to `state` and records what happened.
+    pub fn step(&self, state: &mut State, spanned: Spanned) -> Result<TraceEntry, ScanError> {
+        let (enabled, before) = (state.enabled, state.sum);
+        let applied = self.apply(state, &spanned)?;
+        Ok(TraceEntry {
+            instruction: spanned.instruction,
+            span: spanned.span,
+            enabled,
+            applied,
+            contribution: state.sum - before,
+        })
+    }
+
+    pub fn trace(&self, input: &str) -> Result<Vec<TraceEntry>, ScanError> {
+        let mut state = State::default();
+        self.parser(input).map(|spanned| self.step(&mut state, spanned)).collect()
+    }
+}
+
+/// How `render` marks up the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Highlight {
+    /// Terminal colours: applied instructions in green, disabled ones in
+    /// yellow and everything else dimmed.
+    Ansi,
+    /// `<span class="applied|disabled|ignored">` inside a `<pre>`.
+    Html,
+}
+
+#[derive(Clone, Copy)]
+enum Region {
+    Applied,
+    Disabled,
+    Ignored,
+}
+
+impl Region {
+    fn class(self) -> &'static str {
+        match self {
+            Region::Applied => "applied",
+            Region::Disabled => "disabled",
+            Region::Ignored => "ignored",
+        }
+    }
+
+    fn ansi(self) -> &'static str {
+        match self {
+            Region::Applied => "\x1b[1;32m",
+            Region::Disabled => "\x1b[33m",
+            Region::Ignored => "\x1b[2m",
+        }
+    }
+}
+
+fn push_region(out: &mut String, text: &str, region: Region, highlight: Highlight) {
+    if text.is_empty() {
+        return;
+    }
+    match highlight {
+        Highlight::Ansi => {
+            out.push_str(region.ansi());
+            out.push_str(text);
+            out.push_str("\x1b[0m");
+        }
+        Highlight::Html => {
+            out.push_str("<span class=\"");
+            out.push_str(region.class());
+            out.push_str("\">");
+            for c in text.chars() {
+                match c {
+                    '<' => out.push_str("&lt;"),
+                    '>' => out.push_str("&gt;"),
+                    '&' => out.push_str("&amp;"),
+                    '"' => out.push_str("&quot;"),
+                    _ => out.push(c),
+                }
+            }
+            out.push_str("</span>");
+        }
+    }
+}
+
+/// Prints `input` with the instructions of `trace` marked as applied or
+/// disabled and the bytes between them as ignored. `trace` must come from
+/// scanning this same `input`.
+pub fn render(input: &str, trace: &[TraceEntry], highlight: Highlight) -> String {
+    let mut out = String::with_capacity(input.len() * 2);
+    if highlight == Highlight::Html {
+        out.push_str("<pre>");
+    }
+    let mut pos = 0;
+    for entry in trace {
+        push_region(&mut out, &input[pos..entry.span.start], Region::Ignored, highlight);
+        let region = if entry.applied { Region::Applied } else { Region::Disabled };
+        push_region(&mut out, &input[entry.span.clone()], region, highlight);
+        pos = entry.span.end;
+    }
+    push_region(&mut out, &input[pos..], Region::Ignored, highlight);
+    if highlight == Highlight::Html {
+        out.push_str("</pre>");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    #[test]
+    fn traces_example() {
+        let trace = Registry::standard().trace(EXAMPLE).unwrap();
+        let summary: Vec<(usize, bool, bool, i64)> =
+            trace.iter().map(|e| (e.span.start, e.enabled, e.applied, e.contribution)).collect();
+        assert_eq!(summary, vec![
+            (1, true, true, 8),
+            (20, true, true, 0),
+            (28, false, false, 0),
+            (48, false, false, 0),
+            (59, false, true, 0),
+            (64, true, true, 40),
+        ]);
+        assert_eq!(trace[0].to_string(), "       1  mul(2,4)         on  +8");
+    }
+
+    #[test]
+    fn renders_regions() {
+        let input = "a<mul(1,2)don't()mul(3,4)";
+        let trace = Registry::standard().trace(input).unwrap();
+        assert_eq!(render(input, &trace, Highlight::Html), concat!(
+            r#"<pre><span class="ignored">a&lt;</span><span class="applied">mul(1,2)</span>"#,
+            r#"<span class="applied">don't()</span><span class="disabled">mul(3,4)</span></pre>"#,
+        ));
+        assert_eq!(
+            render("mul(1,2)x", &Registry::standard().trace("mul(1,2)x").unwrap(), Highlight::Ansi),
+            "\x1b[1;32mmul(1,2)\x1b[0m\x1b[2mx\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+
+use rayon::prelude::*;
+
+use crate::{Matrix, DIRECTIONS};
+
+/// One bit per cell for each letter of interest, row by row, so a word can
+/// be matched along a whole row at once with shifted ANDs. Each row of a
+/// plane is only as long as its row of the grid; cells past its end are unset.
+pub struct BitGrid {
+    rows: usize,
+    columns: usize,
+    words_per_row: usize,
+    planes: HashMap<char, Vec<Vec<u64>>>,
+}
+
+/// `out` bit `j` becomes `row` bit `j + by`; bits shifted in from outside the
+/// row are zero.
+fn shift_into(row: &[u64], by: i64, out: &mut [u64]) {
+    let (q, r) = ((by.unsigned_abs() / 64) as usize, (by.unsigned_abs() % 64) as u32);
+    let word = |x: Option<usize>| x.and_then(|x| row.get(x)).copied().unwrap_or(0);
+    for (x, slot) in out.iter_mut().enumerate() {
+        *slot = if by >= 0 {
+            let low = word(x.checked_add(q)) >> r;
+            let high = if r == 0 { 0 } else { word(x.checked_add(q + 1)) << (64 - r) };
+            low | high
+        } else {
+            let high = word(x.checked_sub(q)) << r;
+            let low = if r == 0 { 0 } else { word(x.checked_sub(q + 1)) >> (64 - r) };
+            high | low
+        };
+    }
+}
+
+impl BitGrid {
+    /// Builds planes for the given letters only; other letters are ignored.
+    pub fn new(matrix: &Matrix, letters: impl IntoIterator<Item = char>) -> Self {
+        let mut grid = BitGrid::empty(letters);
+        matrix.iter().for_each(|row| grid.push_row(row.iter().copied()));
+        grid
+    }
+
+    /// Like `new`, reading one line per row straight into the planes, so the
+    /// letters themselves are never all held in memory.
+    pub fn from_reader<R: BufRead>(reader: R, letters: impl IntoIterator<Item = char>) -> io::Result<Self> {
+        let mut grid = BitGrid::empty(letters);
+        for line in reader.lines() {
+            grid.push_row(line?.chars());
+        }
+        Ok(grid)
+    }
+
+    fn empty(letters: impl IntoIterator<Item = char>) -> Self {
+        let planes = letters.into_iter().map(|letter| (letter, Vec::new())).collect();
+        BitGrid { rows: 0, columns: 0, words_per_row: 0, planes }
+    }
+
+    fn push_row(&mut self, row: impl Iterator<Item = char> + Clone) {
+        let width = row.clone().count();
+        self.planes.values_mut().for_each(|plane| plane.push(vec![0; width.div_ceil(64)]));
+        let i = self.rows;
+        for (j, letter) in row.enumerate() {
+            if let Some(plane) = self.planes.get_mut(&letter) {
+                plane[i][j / 64] |= 1 << (j % 64);
+            }
+        }
+        self.rows += 1;
+        self.columns = self.columns.max(width);
+        self.words_per_row = self.columns.div_ceil(64);
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Row `i` of `letter`'s plane, which may be shorter than `words_per_row`.
+    fn row(&self, letter: char, i: usize) -> Option<&[u64]> {
+        Some(&self.planes.get(&letter)?[i])
+    }
+
+    /// Counts `word` in all eight directions, matching what
+    /// `WordSearch::count` gives for that single word. Rows are shared out
+    /// across threads. Letters of `word` without a plane never match.
+    pub fn count(&self, word: &str) -> usize {
+        let letters: Vec<char> = word.chars().collect();
+        if letters.is_empty() {
+            return 0;
+        }
+        let last = letters.len() as i64 - 1;
+        DIRECTIONS.iter().map(|&(di, dj)| {
+            (0..self.rows).into_par_iter()
+                .map_init(
+                    || (vec![0u64; self.words_per_row], vec![0u64; self.words_per_row]),
+                    |(acc, shifted), i| {
+                        let end = i as i64 + last * di as i64;
+                        if end < 0 || end >= self.rows as i64 {
+                            return 0;
+                        }
+                        acc.fill(u64::MAX);
+                        for (k, &letter) in letters.iter().enumerate() {
+                            let row = (i as i64 + k as i64 * di as i64) as usize;
+                            let Some(bits) = self.row(letter, row) else { return 0 };
+                            shift_into(bits, k as i64 * dj as i64, shifted);
+                            acc.iter_mut().zip(shifted.iter()).for_each(|(a, s)| *a &= s);
+                        }
+                        acc.iter().map(|w| w.count_ones() as usize).sum::<usize>()
+                    },
+                )
+                .sum::<usize>()
+        }).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WordSearch;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn shifts_across_words() {
+        let row = [1u64 << 63, 1];
+        let mut out = [0u64; 2];
+        shift_into(&row, 1, &mut out);
+        assert_eq!(out, [0b11 << 62, 0]);
+        shift_into(&row, -1, &mut out);
+        assert_eq!(out, [0, 0b11]);
+        shift_into(&row, 64, &mut out);
+        assert_eq!(out, [1, 0]);
+        shift_into(&row, -65, &mut out);
+        assert_eq!(out, [0, 0]);
+    }
+
+    #[test]
+    fn agrees_with_word_search() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for (rows, columns) in [(1, 1), (3, 70), (70, 3), (40, 130), (129, 64)] {
+            let matrix: Matrix = (0..rows)
+                .map(|_| (0..columns).map(|_| ['X', 'M', 'A', 'S'][rng.gen_range(0..4)]).collect())
+                .collect();
+            for word in ["XMAS", "SAS", "M", "MASSAM"] {
+                let grid = BitGrid::new(&matrix, word.chars());
+                assert_eq!(grid.count(word), WordSearch::new([word]).count(&matrix), "{} on {}x{}", word, rows, columns);
+            }
+        }
+        let ragged: Matrix = vec!["XMAS".chars().collect(), "M".chars().collect(), "AXMAS".chars().collect()];
+        assert_eq!(BitGrid::new(&ragged, "XMAS".chars()).count("XMAS"), WordSearch::new(["XMAS"]).count(&ragged));
+        let text = "S\nXMASXMASXMASXMASXMASXMASXMASXMASXMASXMASXMASXMASXMASXMASXMASXMASXMAS\nMA\nA\nS\n";
+        let read = BitGrid::from_reader(text.as_bytes(), "XMAS".chars()).unwrap();
+        let matrix: Matrix = text.lines().map(|line| line.chars().collect()).collect();
+        assert_eq!((read.rows(), read.columns()), (5, 68));
+        assert_eq!(read.count("XMAS"), WordSearch::new(["XMAS"]).count(&matrix));
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::{Direction, Matrix, WordMatch, WordSearch, DIRECTIONS};
+
+pub struct PuzzleOptions {
+    pub rows: usize,
+    pub columns: usize,
+    /// Directions words may be placed in, each one of `DIRECTIONS`. The
+    /// finished grid is still checked in all eight, so no word shows up
+    /// anywhere else by accident.
+    pub directions: Vec<Direction>,
+    /// Layouts to try before giving up.
+    pub attempts: usize,
+}
+
+impl Default for PuzzleOptions {
+    fn default() -> Self {
+        PuzzleOptions { rows: 10, columns: 10, directions: DIRECTIONS.to_vec(), attempts: 100 }
+    }
+}
+
+/// A generated board and where each word was put.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle<'w> {
+    pub grid: Matrix,
+    pub placements: Vec<WordMatch<'w>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GenerateError {
+    /// An allowed direction is not one of `DIRECTIONS`, so words placed
+    /// along it could never be found.
+    InvalidDirection(Direction),
+    /// No direction is allowed, so no word can be placed.
+    NoDirections,
+    /// There are no words, so there is nothing to fill the board with.
+    NoWords,
+    /// Words of one letter cannot be told apart from the filler.
+    WordTooShort(String),
+    /// The word is longer than the board in every allowed direction.
+    WordDoesNotFit(String),
+    /// No layout in the allowed attempts had each word exactly as often as
+    /// intended, e.g. because one word contains another.
+    NoExactLayout { attempts: usize },
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::InvalidDirection(direction) => write!(f, "{:?} is not a search direction", direction),
+            GenerateError::NoDirections => write!(f, "no directions to place words in"),
+            GenerateError::NoWords => write!(f, "no words to place"),
+            GenerateError::WordTooShort(word) => write!(f, "word {:?} is too short", word),
+            GenerateError::WordDoesNotFit(word) => write!(f, "word {:?} does not fit on the board", word),
+            GenerateError::NoExactLayout { attempts } => write!(f, "no exact layout found in {} attempts", attempts),
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {}
+
+/// How many times `word` is found in one placement: a palindrome also reads
+/// the same backwards.
+fn finds_per_placement(word: &str) -> usize {
+    if word.chars().eq(word.chars().rev()) { 2 } else { 1 }
+}
+
+/// How many cells past its first letter a word of `len` letters reaches
+/// along an axis it moves on by `d`, which is -1, 0 or 1.
+fn extent(len: usize, d: i32) -> usize {
+    if d == 0 { 0 } else { len.saturating_sub(1) }
+}
+
+fn fits(rows: usize, columns: usize, len: usize, (di, dj): Direction) -> bool {
+    len > 0 && extent(len, di) < rows && extent(len, dj) < columns
+}
+
+/// Letters placed so far; `None` where no word has been put yet.
+type Layout = Vec<Vec<Option<char>>>;
+
+/// Tries to place every word, longest first, at random spots where it only
+/// crosses matching letters. Returns `None` if some word finds no spot.
+fn place<'w>(words: &[&'w str], options: &PuzzleOptions, rng: &mut StdRng) -> Option<(Layout, Vec<WordMatch<'w>>)> {
+    let (rows, columns) = (options.rows, options.columns);
+    let mut cells = vec![vec![None; columns]; rows];
+    let mut placements = Vec::with_capacity(words.len());
+    let mut order = words.to_vec();
+    order.shuffle(rng);
+    order.sort_by_key(|word| std::cmp::Reverse(word.chars().count()));
+    for word in order {
+        let letters: Vec<char> = word.chars().collect();
+        let mut spots = Vec::new();
+        for &direction in &options.directions {
+            if !fits(rows, columns, letters.len(), direction) {
+                continue;
+            }
+            let span = |d: i32, size: usize| {
+                let extent = extent(letters.len(), d);
+                if d < 0 { extent..size } else { 0..size - extent }
+            };
+            for i in span(direction.0, rows) {
+                for j in span(direction.1, columns) {
+                    spots.push(((i, j), direction));
+                }
+            }
+        }
+        spots.shuffle(rng);
+        let at = |(i, j): (usize, usize), (di, dj): Direction, k: usize| {
+            ((i as i64 + k as i64 * di as i64) as usize, (j as i64 + k as i64 * dj as i64) as usize)
+        };
+        // A spot whose letters are all in place already would hide the word
+        // inside another one, or on top of an earlier copy of itself.
+        let &(start, direction) = spots.iter().find(|&&(start, direction)| {
+            let under: Vec<Option<char>> = (0..letters.len()).map(|k| {
+                let (i, j) = at(start, direction, k);
+                cells[i][j]
+            }).collect();
+            under.iter().zip(&letters).all(|(cell, &letter)| cell.is_none_or(|c| c == letter))
+                && under.iter().any(Option::is_none)
+        })?;
+        for (k, &letter) in letters.iter().enumerate() {
+            let (i, j) = at(start, direction, k);
+            cells[i][j] = Some(letter);
+        }
+        placements.push(WordMatch { word, start, direction });
+    }
+    Some((cells, placements))
+}
+
+/// Builds a `rows` by `columns` board holding each of `words` once. Cells no
+/// word uses are filled with letters of the words themselves, so partial
+/// words act as decoys. The board is accepted only once `WordSearch` finds
+/// each word exactly as often as it was placed.
+pub fn generate_puzzle<'w>(words: &[&'w str], options: &PuzzleOptions, seed: u64) -> Result<Puzzle<'w>, GenerateError> {
+    if let Some(&direction) = options.directions.iter().find(|d| !DIRECTIONS.contains(d)) {
+        return Err(GenerateError::InvalidDirection(direction));
+    }
+    if options.directions.is_empty() {
+        return Err(GenerateError::NoDirections);
+    }
+    if words.is_empty() {
+        return Err(GenerateError::NoWords);
+    }
+    for word in words {
+        if word.chars().count() < 2 {
+            return Err(GenerateError::WordTooShort(word.to_string()));
+        }
+        if !options.directions.iter().any(|&d| fits(options.rows, options.columns, word.chars().count(), d)) {
+            return Err(GenerateError::WordDoesNotFit(word.to_string()));
+        }
+    }
+    let mut intended: HashMap<&str, usize> = HashMap::new();
+    words.iter().for_each(|word| *intended.entry(word).or_default() += finds_per_placement(word));
+
+    let alphabet: Vec<char> = {
+        let mut letters: Vec<char> = words.iter().flat_map(|word| word.chars()).collect();
+        letters.sort();
+        letters.dedup();
+        letters
+    };
+    let search = WordSearch::new(words);
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..options.attempts {
+        let Some((cells, placements)) = place(words, options, &mut rng) else { continue };
+        let grid: Matrix = cells.into_iter()
+            .map(|row| row.into_iter().map(|c| c.unwrap_or_else(|| alphabet[rng.gen_range(0..alphabet.len())])).collect())
+            .collect();
+        let mut found: HashMap<&str, usize> = HashMap::new();
+        search.find_all(&grid).iter().for_each(|m| *found.entry(m.word).or_default() += 1);
+        if found == intended {
+            return Ok(Puzzle { grid, placements });
+        }
+    }
+    Err(GenerateError::NoExactLayout { attempts: options.attempts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_occur_exactly_once() {
+        let words = ["XMAS", "SANTA", "SLEIGH", "ELF", "SNOW", "LEVEL"];
+        for seed in 0..10 {
+            let puzzle = generate_puzzle(&words, &PuzzleOptions::default(), seed).unwrap();
+            assert_eq!(puzzle.grid.len(), 10);
+            assert!(puzzle.grid.iter().all(|row| row.len() == 10));
+            assert_eq!(puzzle.placements.len(), words.len());
+            for placement in &puzzle.placements {
+                let spelled: String = placement.cells().iter().map(|&(i, j)| puzzle.grid[i][j]).collect();
+                assert_eq!(spelled, placement.word);
+            }
+            // LEVEL is also found backwards.
+            assert_eq!(WordSearch::new(words).count(&puzzle.grid), words.len() + 1);
+            assert_eq!(generate_puzzle(&words, &PuzzleOptions::default(), seed).unwrap(), puzzle);
+        }
+    }
+
+    #[test]
+    fn respects_allowed_directions() {
+        let options = PuzzleOptions { rows: 6, columns: 8, directions: vec![(0, 1), (1, 0)], attempts: 100 };
+        let puzzle = generate_puzzle(&["XMAS", "TREE", "STAR"], &options, 7).unwrap();
+        assert!(puzzle.placements.iter().all(|p| options.directions.contains(&p.direction)));
+    }
+
+    #[test]
+    fn repeated_words_are_placed_apart() {
+        let words = ["XMAS", "XMAS", "LEVEL", "LEVEL"];
+        let puzzle = generate_puzzle(&words, &PuzzleOptions::default(), 3).unwrap();
+        assert_eq!(puzzle.placements.len(), 4);
+        let search = WordSearch::new(words);
+        let count = |word: &str| search.find_all(&puzzle.grid).iter().filter(|m| m.word == word).count();
+        assert_eq!((count("XMAS"), count("LEVEL")), (2, 4));
+    }
+
+    #[test]
+    fn impossible_word_lists() {
+        let options = PuzzleOptions::default();
+        assert_eq!(generate_puzzle(&[], &options, 0), Err(GenerateError::NoWords));
+        assert_eq!(generate_puzzle(&["A"], &options, 0), Err(GenerateError::WordTooShort("A".to_string())));
+        let long = "ABCDEFGHIJK";
+        assert_eq!(generate_puzzle(&[long], &options, 0), Err(GenerateError::WordDoesNotFit(long.to_string())));
+        let skipping = PuzzleOptions { directions: vec![(0, 2)], ..PuzzleOptions::default() };
+        assert_eq!(generate_puzzle(&["ABCDEFG"], &skipping, 1), Err(GenerateError::InvalidDirection((0, 2))));
+        let nowhere = PuzzleOptions { directions: Vec::new(), ..PuzzleOptions::default() };
+        assert_eq!(generate_puzzle(&["XMAS"], &nowhere, 0), Err(GenerateError::NoDirections));
+        // "CAT" is always found again inside "CATS".
+        let options = PuzzleOptions { attempts: 5, ..PuzzleOptions::default() };
+        assert_eq!(generate_puzzle(&["CAT", "CATS"], &options, 0), Err(GenerateError::NoExactLayout { attempts: 5 }));
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::Matrix;
+
+/// What to do with rows that are not as long as the first one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ragged {
+    /// Fail with `GridError::RaggedRow`.
+    Reject,
+    /// Pad every row on the right to the longest one with the given letter.
+    Pad(char),
+    /// Keep the rows as they are; every search treats the missing cells as
+    /// outside the grid.
+    Keep,
+}
+
+/// What one cell of the grid holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cells {
+    Chars,
+    /// User-perceived characters, so a letter with combining marks or a
+    /// multi-codepoint emoji takes up a single cell.
+    Graphemes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridOptions {
+    pub ragged: Ragged,
+    pub cells: Cells,
+}
+
+impl Default for GridOptions {
+    fn default() -> Self {
+        GridOptions { ragged: Ragged::Reject, cells: Cells::Graphemes }
+    }
+}
+
+/// Why a grid could not be loaded. Lines are 1-based.
+#[derive(Debug)]
+pub enum GridError {
+    Io(io::Error),
+    RaggedRow { line: usize, expected: usize, found: usize },
+    /// The grid has more distinct multi-`char` clusters than there are
+    /// private-use characters to store them as.
+    TooManyClusters { line: usize },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Io(error) => write!(f, "read failed: {}", error),
+            GridError::RaggedRow { line, expected, found } => {
+                write!(f, "line {}: expected {} cells, found {}", line, expected, found)
+            }
+            GridError::TooManyClusters { line } => {
+                write!(f, "line {}: too many distinct grapheme clusters", line)
+            }
+        }
+    }
+}
+
+impl Error for GridError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GridError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Maps the grapheme clusters of a grid to the single `char` each one is
+/// stored as in the `Matrix`. A one-`char` cluster is stored as itself; a
+/// longer one gets a private-use character that appears nowhere else.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Alphabet {
+    letters: HashMap<String, char>,
+    clusters: HashMap<char, String>,
+}
+
+impl Alphabet {
+    /// The letter a cell holding `cluster` is stored as. Clusters of several
+    /// `char`s that are not on the grid have none.
+    pub fn letter(&self, cluster: &str) -> Option<char> {
+        if let Some(&letter) = self.letters.get(cluster) {
+            return Some(letter);
+        }
+        let mut chars = cluster.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if !self.clusters.contains_key(&c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// `word` in the letters of the grid, ready for `WordSearch` or
+    /// `Stencil`; `None` if it uses a cluster the grid does not have, in
+    /// which case it cannot be found anyway.
+    pub fn encode(&self, word: &str) -> Option<String> {
+        word.graphemes(true).map(|cluster| self.letter(cluster)).collect()
+    }
+
+    /// The text a stored letter stands for.
+    pub fn cluster(&self, letter: char) -> String {
+        self.clusters.get(&letter).cloned().unwrap_or_else(|| letter.to_string())
+    }
+}
+
+/// A loaded grid and how to read its letters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    pub matrix: Matrix,
+    pub alphabet: Alphabet,
+}
+
+impl Grid {
+    pub fn rows(&self) -> usize {
+        self.matrix.len()
+    }
+
+    /// Length of the longest row.
+    pub fn columns(&self) -> usize {
+        self.matrix.iter().map(Vec::len).max().unwrap_or(0)
+    }
+}
+
+/// Where the letters for multi-`char` clusters come from: Supplementary
+/// Private Use Area-A.
+const PRIVATE_USE: std::ops::RangeInclusive<u32> = 0xF0000..=0xFFFFD;
+
+/// Hands out private-use letters for clusters, skipping those the grid
+/// itself holds as single-`char` cells.
+#[derive(Default)]
+struct Letters {
+    alphabet: Alphabet,
+    /// Private-use characters seen as cells of their own.
+    taken: HashSet<char>,
+    /// How many private-use characters have been tried.
+    tried: u32,
+}
+
+impl Letters {
+    fn spare(&mut self, line: usize) -> Result<char, GridError> {
+        loop {
+            let code = PRIVATE_USE.start() + self.tried;
+            if !PRIVATE_USE.contains(&code) {
+                return Err(GridError::TooManyClusters { line });
+            }
+            self.tried += 1;
+            match char::from_u32(code) {
+                Some(letter) if !self.taken.contains(&letter) => return Ok(letter),
+                _ => {}
+            }
+        }
+    }
+
+    /// The letter a cell is stored as. A cluster that turns out to have been
+    /// given a letter the grid also uses on its own gets a new one, and the
+    /// cells already read (`matrix` and the start of `row`) are relabelled.
+    fn letter(&mut self, cluster: &str, matrix: &mut Matrix, row: &mut [char]) -> Result<char, GridError> {
+        let line = matrix.len() + 1;
+        let mut chars = cluster.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if PRIVATE_USE.contains(&(c as u32)) && self.taken.insert(c) {
+                if let Some(moved) = self.alphabet.clusters.remove(&c) {
+                    let letter = self.spare(line)?;
+                    for cell in matrix.iter_mut().flatten().chain(row.iter_mut()) {
+                        if *cell == c {
+                            *cell = letter;
+                        }
+                    }
+                    self.alphabet.letters.insert(moved.clone(), letter);
+                    self.alphabet.clusters.insert(letter, moved);
+                }
+            }
+            return Ok(c);
+        }
+        if let Some(&letter) = self.alphabet.letters.get(cluster) {
+            return Ok(letter);
+        }
+        let letter = self.spare(line)?;
+        self.alphabet.letters.insert(cluster.to_string(), letter);
+        self.alphabet.clusters.insert(letter, cluster.to_string());
+        Ok(letter)
+    }
+}
+
+/// Reads a grid one row per line. Trailing blank lines are dropped, so an
+/// empty input gives an empty grid rather than an error, and a grid of one
+/// row or one column is as valid as any other. Only one line of text is
+/// held at a time; each row is stored as its letters straight away.
+pub fn load_grid<R: BufRead>(mut reader: R, options: &GridOptions) -> Result<Grid, GridError> {
+    let mut matrix: Matrix = Vec::new();
+    let mut letters = Letters::default();
+    // Blank lines read since the last row with cells; they only become rows
+    // once another such row follows.
+    let mut blank = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(GridError::Io)? == 0 {
+            break;
+        }
+        let text = line.strip_suffix('\n').map_or(line.as_str(), |text| text.strip_suffix('\r').unwrap_or(text));
+        if text.is_empty() {
+            blank += 1;
+            continue;
+        }
+        for _ in 0..std::mem::take(&mut blank) {
+            push_row(&mut matrix, Vec::new(), options.ragged)?;
+        }
+        let mut row = Vec::with_capacity(text.len());
+        match options.cells {
+            Cells::Chars => row.extend(text.chars()),
+            Cells::Graphemes => {
+                for cluster in text.graphemes(true) {
+                    let letter = letters.letter(cluster, &mut matrix, &mut row)?;
+                    row.push(letter);
+                }
+            }
+        }
+        row.shrink_to_fit();
+        push_row(&mut matrix, row, options.ragged)?;
+    }
+    if let Ragged::Pad(fill) = options.ragged {
+        let columns = matrix.iter().map(Vec::len).max().unwrap_or(0);
+        matrix.iter_mut().for_each(|row| row.resize(columns, fill));
+    }
+    Ok(Grid { matrix, alphabet: letters.alphabet })
+}
+
+fn push_row(matrix: &mut Matrix, row: Vec<char>, ragged: Ragged) -> Result<(), GridError> {
+    if let (Ragged::Reject, Some(first)) = (ragged, matrix.first()) {
+        if row.len() != first.len() {
+            return Err(GridError::RaggedRow { line: matrix.len() + 1, expected: first.len(), found: row.len() });
+        }
+    }
+    matrix.push(row);
+    Ok(())
+}
+
+pub fn load_grid_from_file(file_path: &str, options: &GridOptions) -> Result<Grid, GridError> {
+    let file = File::open(file_path).map_err(GridError::Io)?;
+    load_grid(io::BufReader::new(file), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Stencil, Symmetry, WordSearch};
+
+    #[test]
+    fn ragged_rows() {
+        let input = "XMAS\nXM\nXMA\n";
+        let error = load_grid(input.as_bytes(), &GridOptions::default()).unwrap_err();
+        assert!(matches!(error, GridError::RaggedRow { line: 2, expected: 4, found: 2 }));
+
+        let pad = GridOptions { ragged: Ragged::Pad('.'), ..GridOptions::default() };
+        let grid = load_grid(input.as_bytes(), &pad).unwrap();
+        assert_eq!(grid.matrix[1], vec!['X', 'M', '.', '.']);
+
+        let keep = GridOptions { ragged: Ragged::Keep, ..GridOptions::default() };
+        assert_eq!(load_grid(input.as_bytes(), &keep).unwrap().matrix[2].len(), 3);
+    }
+
+    #[test]
+    fn degenerate_sizes() {
+        let search = WordSearch::new(["XMAS"]);
+        let x = Stencil::new(&["M.S", ".A.", "M.S"], '.');
+        for input in ["", "\n\n", "X", "XMAS", "X\r\nM\r\nA\r\nS\r\n", "MAS\nAAA\n"] {
+            let grid = load_grid(input.as_bytes(), &GridOptions::default()).unwrap();
+            let expected = usize::from(input.contains("XMAS") || input.contains("X\r\nM"));
+            assert_eq!(search.count(&grid.matrix), expected, "{:?}", input);
+            assert_eq!(x.count(&grid.matrix, Symmetry::Distinct), 0);
+        }
+        assert_eq!(load_grid("\n\n".as_bytes(), &GridOptions::default()).unwrap().rows(), 0);
+    }
+
+    #[test]
+    fn grapheme_cells() {
+        // "é" written as "e" plus a combining accent, and a flag of two code points.
+        let input = "ce\u{301}\u{1F1EB}\u{1F1F7}\nxyz\n";
+        let grid = load_grid(input.as_bytes(), &GridOptions::default()).unwrap();
+        assert_eq!((grid.rows(), grid.columns()), (2, 3));
+        let word = grid.alphabet.encode("ce\u{301}").unwrap();
+        assert_eq!(WordSearch::new([word]).count(&grid.matrix), 1);
+        assert_eq!(grid.alphabet.cluster(grid.matrix[0][2]), "\u{1F1EB}\u{1F1F7}");
+        assert_eq!(grid.alphabet.encode("a\u{301}"), None);
+
+        let chars = GridOptions { cells: Cells::Chars, ragged: Ragged::Keep };
+        assert_eq!(load_grid(input.as_bytes(), &chars).unwrap().matrix[0].len(), 5);
+    }
+
+    #[test]
+    fn too_many_clusters() {
+        // Every CJK ideograph with one of four accents: more distinct
+        // clusters than Private Use Area-A has characters.
+        let clusters = (0x4E00..=0x9FFF).filter_map(char::from_u32)
+            .flat_map(|c| ['\u{300}', '\u{301}', '\u{302}', '\u{303}'].map(|accent| format!("{}{}", c, accent)));
+        let input = format!("ab\n{}\n", clusters.take(65_535).collect::<String>());
+        let keep = GridOptions { ragged: Ragged::Keep, ..GridOptions::default() };
+        let error = load_grid(input.as_bytes(), &keep).unwrap_err();
+        assert!(matches!(error, GridError::TooManyClusters { line: 2 }));
+    }
+
+    #[test]
+    fn private_use_cells_keep_their_letter() {
+        // The cluster is seen first and given U+F0000, which a later cell holds on its own.
+        let input = "e\u{301}x\nx\u{F0000}\n";
+        let grid = load_grid(input.as_bytes(), &GridOptions::default()).unwrap();
+        assert_eq!(grid.matrix[1], vec!['x', '\u{F0000}']);
+        assert_ne!(grid.matrix[0][0], '\u{F0000}');
+        assert_eq!(grid.alphabet.cluster(grid.matrix[0][0]), "e\u{301}");
+        assert_eq!(grid.alphabet.encode("\u{F0000}x"), Some("\u{F0000}x".to_string()));
+        assert_eq!(grid.alphabet.letter("e\u{301}"), Some(grid.matrix[0][0]));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{Matrix, WordSearch};
+
+/// A stack of letter grids, indexed as `[layer][row][column]`.
+pub type Layers = Vec<Matrix>;
+
+/// A step to a neighbouring cell, as (layer, row, column) deltas.
+pub type Direction3 = (i32, i32, i32);
+
+/// The 26 steps to the cells around a cell in a stack of layers.
+pub fn directions_3d() -> impl Iterator<Item = Direction3> {
+    (-1..=1)
+        .flat_map(|dl| (-1..=1).flat_map(move |di| (-1..=1).map(move |dj| (dl, di, dj))))
+        .filter(|&step| step != (0, 0, 0))
+}
+
+/// One occurrence of a word in a stack of layers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WordMatch3<'s> {
+    pub word: &'s str,
+    /// Layer, row and column of the word's first letter.
+    pub start: (usize, usize, usize),
+    pub direction: Direction3,
+}
+
+fn cell(layers: &Layers, (l, i, j): (i64, i64, i64)) -> Option<char> {
+    let layer = layers.get(usize::try_from(l).ok()?)?;
+    let row = layer.get(usize::try_from(i).ok()?)?;
+    row.get(usize::try_from(j).ok()?).copied()
+}
+
+impl WordSearch {
+    /// Every match along each of the 26 directions through the stack. Each
+    /// layer on its own gives the same matches as `find_all`.
+    pub fn find_all_3d(&self, layers: &Layers) -> Vec<WordMatch3<'_>> {
+        let mut matches = Vec::new();
+        let mut letters = Vec::new();
+        let cells: Vec<(i64, i64, i64)> = layers.iter().enumerate()
+            .flat_map(|(l, layer)| layer.iter().enumerate().map(move |(i, row)| (l, i, row.len())))
+            .flat_map(|(l, i, n)| (0..n).map(move |j| (l as i64, i as i64, j as i64)))
+            .collect();
+        for direction in directions_3d() {
+            let (dl, di, dj) = (direction.0 as i64, direction.1 as i64, direction.2 as i64);
+            // Lines start where the previous cell in `direction` is missing.
+            for &(l, i, j) in &cells {
+                if cell(layers, (l - dl, i - di, j - dj)).is_some() {
+                    continue;
+                }
+                letters.clear();
+                let mut at = (l, i, j);
+                while let Some(letter) = cell(layers, at) {
+                    letters.push(letter);
+                    at = (at.0 + dl, at.1 + di, at.2 + dj);
+                }
+                self.scan_line(&letters, false, |k, word| {
+                    let k = k as i64;
+                    let start = ((l + k * dl) as usize, (i + k * di) as usize, (j + k * dj) as usize);
+                    matches.push(WordMatch3 { word, start, direction });
+                });
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layers(layers: &[&[&str]]) -> Layers {
+        layers.iter().map(|rows| rows.iter().map(|row| row.chars().collect()).collect()).collect()
+    }
+
+    #[test]
+    fn searches_through_layers() {
+        assert_eq!(directions_3d().count(), 26);
+        let stack = layers(&[
+            &["CAT", "...", "..."],
+            &["...", ".A.", "..."],
+            &["...", "...", "..T"],
+        ]);
+        let search = WordSearch::new(["CAT"]);
+        let mut found: Vec<_> = search.find_all_3d(&stack).iter().map(|m| (m.start, m.direction)).collect();
+        found.sort();
+        assert_eq!(found, vec![((0, 0, 0), (0, 0, 1)), ((0, 0, 0), (1, 1, 1))]);
+
+        let flat = layers(&[&["CAT", "A..", "T.."]]);
+        assert_eq!(search.find_all_3d(&flat).len(), search.count(&flat[0]));
+    }
+}
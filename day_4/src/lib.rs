@@ -1,87 +1,93 @@
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::collections::{BTreeSet, HashMap};
 
-type Matrix = Vec<Vec<char>>;
+pub mod bitboard;
+pub mod generator;
+pub mod grid;
+pub mod layers;
+pub mod render;
+pub mod search;
+pub mod stencil;
 
-fn read_input_from_file(file_path: &str) -> io::Result<Matrix> {
-    // Open the file
-    let file = File::open(file_path)?;
-    let reader = io::BufReader::new(file);
+pub use bitboard::BitGrid;
+pub use generator::{generate_puzzle, GenerateError, Puzzle, PuzzleOptions};
+pub use grid::{load_grid, load_grid_from_file, Alphabet, Cells, Grid, GridError, GridOptions, Ragged};
+pub use layers::{directions_3d, Direction3, Layers, WordMatch3};
+pub use render::{render, Highlight};
+pub use search::{Direction, Topology, WordMatch, WordSearch, DIRECTIONS};
+pub use stencil::{Orientation, Stencil, StencilMatch, Symmetry};
 
-    // Create the output vector
-    let output: Matrix = reader.lines().map(|line| {
-        line.expect("").chars().collect::<Vec<char>>()
-    }).collect();
-    Ok(output)
-}
+pub type Matrix = Vec<Vec<char>>;
 
+/// The cells of one match, as (row, column).
+pub type CellSet = BTreeSet<(usize, usize)>;
 
-fn match_in_direction(matrix: &Matrix, i: usize, j: usize, di: i32, dj: i32) -> i32 {
-    let m = matrix.len() as i32;
-    let n = matrix[0].len() as i32;
-    let ii = i as i32;
-    let jj = j as i32;
-    if  ii + 3 * di >= 0 &&
-        ii + 3 * di < m &&
-        jj + 3 * dj >= 0 &&
-        jj + 3 * dj < n &&
-        matrix[i][j] == 'X' &&
-        matrix[(ii + di) as usize][(jj + dj) as usize] == 'M' &&
-        matrix[(ii + di + di) as usize][(jj + dj + dj) as usize] == 'A' &&
-        matrix[(ii + di + di + di) as usize][(jj + dj + dj + dj) as usize] == 'S' { 1 } else { 0 }
+fn read_input_from_file(file_path: &str) -> Result<Grid, GridError> {
+    load_grid_from_file(file_path, &GridOptions::default())
 }
 
+pub fn xmas_from_file(file_path: &str) -> i32 {
+    try_xmas_from_file(file_path).expect("")
+}
 
-fn xmas_from_index(matrix: &Matrix, i: usize, j: usize) -> i32 {
-    match_in_direction(matrix, i, j, 0, 1) + 
-    match_in_direction(matrix, i, j, 0, -1) +
-    match_in_direction(matrix, i, j, 1, 0) +
-    match_in_direction(matrix, i, j, -1, 0) +
-    match_in_direction(matrix, i, j, 1, 1) +
-    match_in_direction(matrix, i, j, 1, -1) +
-    match_in_direction(matrix, i, j, -1, 1) +
-    match_in_direction(matrix, i, j, -1, -1)
+pub fn try_xmas_from_file(file_path: &str) -> Result<i32, GridError> {
+    let grid = read_input_from_file(file_path)?;
+    Ok(WordSearch::new(["XMAS"]).count(&grid.matrix) as i32)
 }
 
-fn central_xmas_from_index(matrix: &Matrix, i: i32, j: i32) -> i32 {
-    if matrix[i as usize][j as usize] != 'A' { return 0; }
+/// The grid stored in the file and every occurrence of any of `words` in it.
+/// The words are encoded through the grid's alphabet, so one with a
+/// multi-codepoint cluster is found like any other; each match names the
+/// word as it was given.
+pub fn words_from_file<'w, S: AsRef<str>>(file_path: &str, words: &'w [S]) -> Result<(Grid, Vec<WordMatch<'w>>), GridError> {
+    let grid = read_input_from_file(file_path)?;
+    let encoded: Vec<(String, &'w str)> = words.iter()
+        .filter_map(|word| Some((grid.alphabet.encode(word.as_ref())?, word.as_ref())))
+        .collect();
+    let given: HashMap<&str, &'w str> = encoded.iter().map(|(letters, word)| (letters.as_str(), *word)).collect();
+    let search = WordSearch::new(encoded.iter().map(|(letters, _)| letters));
+    let found = search.find_all(&grid.matrix).iter()
+        .map(|m| WordMatch { word: given[m.word], start: m.start, direction: m.direction })
+        .collect();
+    Ok((grid, found))
+}
 
-    if matrix[(i - 1) as usize][(j - 1) as usize] == 'M' && matrix[(i - 1) as usize][(j + 1) as usize] == 'M' && 
-       matrix[(i + 1) as usize][(j - 1) as usize] == 'S' && matrix[(i + 1) as usize][(j + 1) as usize] == 'S' {return 1;} 
-    if matrix[(i - 1) as usize][(j - 1) as usize] == 'M' && matrix[(i - 1) as usize][(j + 1) as usize] == 'S' && 
-       matrix[(i + 1) as usize][(j - 1) as usize] == 'M' && matrix[(i + 1) as usize][(j + 1) as usize] == 'S' {return 1;} 
+/// The grid and the cells of each XMAS in it.
+pub fn xmas_locations_from_file(file_path: &str) -> Result<(Grid, Vec<CellSet>), GridError> {
+    let grid = read_input_from_file(file_path)?;
+    let found = WordSearch::new(["XMAS"]).find_all(&grid.matrix).iter()
+        .map(|m| m.cells().into_iter().collect())
+        .collect();
+    Ok((grid, found))
+}
 
-    if matrix[(i - 1) as usize][(j - 1) as usize] == 'S' && matrix[(i - 1) as usize][(j + 1) as usize] == 'S' && 
-       matrix[(i + 1) as usize][(j - 1) as usize] == 'M' && matrix[(i + 1) as usize][(j + 1) as usize] == 'M' {return 1;} 
-    if matrix[(i - 1) as usize][(j - 1) as usize] == 'S' && matrix[(i - 1) as usize][(j + 1) as usize] == 'M' && 
-       matrix[(i + 1) as usize][(j - 1) as usize] == 'S' && matrix[(i + 1) as usize][(j + 1) as usize] == 'M' {return 1;} 
+fn x_mas() -> Stencil {
+    Stencil::new(&["M.S", ".A.", "M.S"], '.')
+}
 
-    0
+pub fn central_xmas_from_file(file_path: &str) -> i32 {
+    try_central_xmas_from_file(file_path).expect("")
 }
 
-pub fn xmas_from_file(file_path: &str) -> i32 {
-    let matrix: Matrix = read_input_from_file(file_path).expect("");
-    let m = matrix.len();
-    let n = matrix[0].len();
-    (0..m)
-        .flat_map(|i| (0..n).map(move |j| (i, j)))
-        .map(|(i, j)| xmas_from_index(&matrix, i, j))
-        .sum()
+pub fn try_central_xmas_from_file(file_path: &str) -> Result<i32, GridError> {
+    let grid = read_input_from_file(file_path)?;
+    Ok(x_mas().count(&grid.matrix, Symmetry::Distinct) as i32)
 }
 
-pub fn central_xmas_from_file(file_path: &str) -> i32 {
-    let matrix: Matrix = read_input_from_file(file_path).expect("");
-    let m = matrix.len();
-    let n = matrix[0].len();
-    (1..m - 1)
-        .flat_map(|i| (1..n - 1).map(move |j| (i, j)))
-        .map(|(i, j)| central_xmas_from_index(&matrix, i as i32, j as i32))
-        .sum()
+/// The grid and the five cells of each X-MAS in it.
+pub fn central_xmas_locations_from_file(file_path: &str) -> Result<(Grid, Vec<CellSet>), GridError> {
+    let grid = read_input_from_file(file_path)?;
+    let stencil = x_mas();
+    let found = stencil.find_all(&grid.matrix, Symmetry::Distinct).iter()
+        .map(|m| stencil.cells(m).into_iter().collect())
+        .collect();
+    Ok((grid, found))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
+    use std::io;
 
     #[test]
     fn it_works() {
@@ -91,4 +97,46 @@ mod tests {
         assert_eq!(central_xmas_from_file("data/example.txt"), 9);
         assert_eq!(central_xmas_from_file("data/input.txt"), 2005);
     }
+
+    #[test]
+    fn bitboard_counts() {
+        let matrix = read_input_from_file("data/input.txt").unwrap().matrix;
+        assert_eq!(BitGrid::new(&matrix, "XMAS".chars()).count("XMAS"), 2639);
+        let file = io::BufReader::new(File::open("data/input.txt").unwrap());
+        let grid = BitGrid::from_reader(file, "XMAS".chars()).unwrap();
+        assert_eq!((grid.rows(), grid.columns()), (matrix.len(), matrix[0].len()));
+        assert_eq!(grid.count("XMAS"), 2639);
+    }
+
+    #[test]
+    fn errors_instead_of_panics() {
+        assert!(matches!(try_xmas_from_file("data/missing.txt"), Err(GridError::Io(_))));
+        assert!(matches!(try_central_xmas_from_file("data/missing.txt"), Err(GridError::Io(_))));
+    }
+
+    #[test]
+    fn locations() {
+        let (grid, found) = xmas_locations_from_file("data/example.txt").unwrap();
+        assert_eq!(found.len(), 18);
+        assert!(found.contains(&BTreeSet::from([(0, 4), (1, 5), (2, 6), (3, 7)])));
+        let cells: Vec<(usize, usize)> = found.iter().flatten().copied().collect();
+        assert_eq!(render(&grid.matrix, &grid.alphabet, &cells, Highlight::Plain).lines().next(), Some("....XXMAS."));
+
+        let (grid, found) = central_xmas_locations_from_file("data/example.txt").unwrap();
+        assert_eq!(found.len(), 9);
+        let cells: Vec<(usize, usize)> = found.iter().flatten().copied().collect();
+        assert_eq!(render(&grid.matrix, &grid.alphabet, &cells, Highlight::Plain).lines().next(), Some(".M.S......"));
+    }
+
+    #[test]
+    fn multi_codepoint_words() {
+        let path = std::env::temp_dir().join(format!("day_4_clusters_{}.txt", std::process::id()));
+        std::fs::write(&path, "ce\u{301}x\nabc\n").unwrap();
+        let words = ["ce\u{301}x", "XMAS"];
+        let (grid, found) = words_from_file(path.to_str().unwrap(), &words).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(found, vec![WordMatch { word: "ce\u{301}x", start: (0, 0), direction: (0, 1) }]);
+        let cells: Vec<(usize, usize)> = found.iter().flat_map(|m| m.cells()).collect();
+        assert_eq!(render(&grid.matrix, &grid.alphabet, &cells, Highlight::Plain), "ce\u{301}x\n...\n");
+    }
 }
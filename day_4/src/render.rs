@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+
+use crate::{Alphabet, Matrix};
+
+/// How `render` shows the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Highlight {
+    /// Matched letters as they are and every other letter as `.`, like the
+    /// puzzle's illustrations.
+    Plain,
+    /// As `Plain`, with matched letters in bold green and the dots dimmed.
+    Ansi,
+}
+
+/// Prints the grid one row per line, keeping only the letters in `cells`,
+/// each written as the cluster `alphabet` says it stands for.
+pub fn render<'c, I>(matrix: &Matrix, alphabet: &Alphabet, cells: I, highlight: Highlight) -> String
+where
+    I: IntoIterator<Item = &'c (usize, usize)>,
+{
+    let keep: HashSet<(usize, usize)> = cells.into_iter().copied().collect();
+    let mut out = String::new();
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, &letter) in row.iter().enumerate() {
+            match (keep.contains(&(i, j)), highlight) {
+                (true, Highlight::Plain) => out.push_str(&alphabet.cluster(letter)),
+                (false, Highlight::Plain) => out.push('.'),
+                (true, Highlight::Ansi) => {
+                    out.push_str("\x1b[1;32m");
+                    out.push_str(&alphabet.cluster(letter));
+                    out.push_str("\x1b[0m");
+                }
+                (false, Highlight::Ansi) => out.push_str("\x1b[2m.\x1b[0m"),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Stencil, Symmetry, WordSearch};
+
+    #[test]
+    fn blanks_unmatched_letters() {
+        let matrix: Matrix = ["MMMSXXMASM", "MSAMXMSMSA", "AMXSXMAAMM"].iter()
+            .map(|row| row.chars().collect())
+            .collect();
+        let search = WordSearch::new(["XMAS"]);
+        let cells: Vec<(usize, usize)> = search.find_all(&matrix).iter().flat_map(|m| m.cells()).collect();
+        assert_eq!(render(&matrix, &Alphabet::default(), &cells, Highlight::Plain), ".....XMAS.\n.SAMX.....\n..........\n");
+
+        let x = Stencil::new(&["M.S", ".A.", "M.S"], '.');
+        let cells: Vec<(usize, usize)> = x.find_all(&matrix, Symmetry::Distinct).iter().flat_map(|m| x.cells(m)).collect();
+        assert_eq!(render(&matrix, &Alphabet::default(), &cells, Highlight::Plain), ".M.S......\n..A.......\n.M.S......\n");
+        assert_eq!(render(&matrix[..1].to_vec(), &Alphabet::default(), &[(0, 0)], Highlight::Ansi).matches("\x1b[2m.").count(), 9);
+    }
+}
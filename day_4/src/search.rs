@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+
+use aho_corasick::AhoCorasick;
+
+use crate::{GridError, Matrix};
+
+/// A step to a neighbouring cell, as (row delta, column delta).
+pub type Direction = (i32, i32);
+
+/// The eight ways a word can be read: across, down and diagonally, both ways.
+pub const DIRECTIONS: [Direction; 8] = [(0, 1), (0, -1), (1, 0), (-1, 0), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+/// How the edges of a grid behave.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// Words stop at the edges.
+    #[default]
+    Flat,
+    /// Leaving one edge re-enters at the opposite one, so every line is a
+    /// loop. A word is never longer than its loop, so no cell is used twice.
+    /// Only rectangular grids can be wrapped.
+    Torus,
+}
+
+/// One occurrence of a word in a grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WordMatch<'s> {
+    pub word: &'s str,
+    /// Row and column of the word's first letter.
+    pub start: (usize, usize),
+    pub direction: Direction,
+}
+
+impl WordMatch<'_> {
+    /// The cells the word covers, from its first letter to its last.
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        let ((i, j), (di, dj)) = (self.start, self.direction);
+        (0..self.word.chars().count() as i64)
+            .map(|k| ((i as i64 + k * di as i64) as usize, (j as i64 + k * dj as i64) as usize))
+            .collect()
+    }
+
+    /// Like `cells`, wrapping around a `rows` by `columns` torus.
+    pub fn cells_on_torus(&self, rows: usize, columns: usize) -> Vec<(usize, usize)> {
+        let ((i, j), (di, dj)) = (self.start, self.direction);
+        let (rows, columns) = (rows as i64, columns as i64);
+        (0..self.word.chars().count() as i64)
+            .map(|k| {
+                let (i, j) = (i as i64 + k * di as i64, j as i64 + k * dj as i64);
+                (i.rem_euclid(rows) as usize, j.rem_euclid(columns) as usize)
+            })
+            .collect()
+    }
+}
+
+/// Finds every occurrence of a list of words in a letter grid. Each line of
+/// the grid in each direction is fed once through a single automaton for all
+/// the words, so the cost does not grow with the number of words.
+pub struct WordSearch {
+    words: Vec<String>,
+    /// Length of each word in letters.
+    lengths: Vec<usize>,
+    automaton: AhoCorasick,
+}
+
+fn cell(matrix: &Matrix, i: i64, j: i64) -> Option<char> {
+    let row = matrix.get(usize::try_from(i).ok()?)?;
+    row.get(usize::try_from(j).ok()?).copied()
+}
+
+/// The cells met walking from every cell whose predecessor in `direction` is
+/// outside the grid, so every cell is on exactly one line.
+fn lines(matrix: &Matrix, (di, dj): Direction) -> impl Iterator<Item = Vec<(usize, usize)>> + '_ {
+    let (di, dj) = (di as i64, dj as i64);
+    (0..matrix.len())
+        .flat_map(|i| (0..matrix[i].len()).map(move |j| (i as i64, j as i64)))
+        .filter(move |&(i, j)| cell(matrix, i - di, j - dj).is_none())
+        .map(move |(mut i, mut j)| {
+            let mut line = Vec::new();
+            while cell(matrix, i, j).is_some() {
+                line.push((i as usize, j as usize));
+                (i, j) = (i + di, j + dj);
+            }
+            line
+        })
+}
+
+/// The directions whose loops differ on a `rows` by `columns` torus. Along a
+/// side of one or two cells, opposite steps (and on one cell, no step at all)
+/// end up on the same cell, so only the first of those in `DIRECTIONS` is kept.
+fn torus_directions(rows: usize, columns: usize) -> Vec<Direction> {
+    let wrap = |(di, dj): Direction| ((di as i64).rem_euclid(rows as i64), (dj as i64).rem_euclid(columns as i64));
+    let mut kept: Vec<Direction> = Vec::new();
+    for direction in DIRECTIONS {
+        if !kept.iter().any(|&other| wrap(other) == wrap(direction)) {
+            kept.push(direction);
+        }
+    }
+    kept
+}
+
+/// The loops through a rectangular torus in `direction`; every cell is on
+/// exactly one.
+fn loops(matrix: &Matrix, (di, dj): Direction) -> Vec<Vec<(usize, usize)>> {
+    let (rows, columns) = (matrix.len(), matrix.first().map_or(0, Vec::len));
+    let step = |i: usize, d: i32, size: usize| (i as i64 + d as i64).rem_euclid(size as i64) as usize;
+    let mut seen = vec![vec![false; columns]; rows];
+    let mut loops = Vec::new();
+    for i in 0..rows {
+        for j in 0..columns {
+            let (mut a, mut b) = (i, j);
+            let mut cycle = Vec::new();
+            while !seen[a][b] {
+                seen[a][b] = true;
+                cycle.push((a, b));
+                (a, b) = (step(a, di, rows), step(b, dj, columns));
+            }
+            if !cycle.is_empty() {
+                loops.push(cycle);
+            }
+        }
+    }
+    loops
+}
+
+impl WordSearch {
+    /// Empty words are left out, since they would match everywhere, and so
+    /// are repeats, which would report every match once per copy.
+    pub fn new<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut seen = HashSet::new();
+        let words: Vec<String> = words.into_iter()
+            .map(|word| word.as_ref().to_string())
+            .filter(|word| !word.is_empty() && seen.insert(word.clone()))
+            .collect();
+        let lengths = words.iter().map(|word| word.chars().count()).collect();
+        let automaton = AhoCorasick::new(&words).expect("word list too large for the automaton");
+        WordSearch { words, lengths, automaton }
+    }
+
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// Calls `found(position, word)` for each word read along `letters`.
+    /// With `cyclic`, the letters form a loop and a word may run from the end
+    /// back round to the start, as long as it is no longer than the loop.
+    pub(crate) fn scan_line<'s>(&'s self, letters: &[char], cyclic: bool, mut found: impl FnMut(usize, &'s str)) {
+        let mut text = String::new();
+        let mut offsets = Vec::with_capacity(letters.len());
+        for &letter in letters {
+            offsets.push(text.len());
+            text.push(letter);
+        }
+        if cyclic {
+            let longest = self.lengths.iter().copied().max().unwrap_or(0);
+            letters.iter().take(longest.saturating_sub(1)).for_each(|&letter| text.push(letter));
+        }
+        for m in self.automaton.find_overlapping_iter(&text) {
+            // Words are valid UTF-8, so they start on a letter; one that starts
+            // in the repeated tail was already found at the start of the loop.
+            let Ok(k) = offsets.binary_search(&m.start()) else { continue };
+            let pattern = m.pattern().as_usize();
+            if self.lengths[pattern] <= letters.len() {
+                found(k, &self.words[pattern]);
+            }
+        }
+    }
+
+    /// Every match in every direction. A palindrome is found once each way,
+    /// and a one-letter word once per direction.
+    pub fn find_all(&self, matrix: &Matrix) -> Vec<WordMatch<'_>> {
+        self.find_on_lines(matrix, &DIRECTIONS, false, |direction| lines(matrix, direction).collect())
+    }
+
+    /// Like `find_all`, on a grid with the given edges. Match starts are
+    /// always inside the grid; use `WordMatch::cells_on_torus` for the cells
+    /// of a wrapped match. A torus must be rectangular, and on one with a side
+    /// of one or two cells, each loop is searched in only one of the
+    /// directions that walk it.
+    pub fn find_all_on(&self, matrix: &Matrix, topology: Topology) -> Result<Vec<WordMatch<'_>>, GridError> {
+        match topology {
+            Topology::Flat => Ok(self.find_all(matrix)),
+            Topology::Torus => {
+                let columns = matrix.first().map_or(0, Vec::len);
+                if let Some(i) = matrix.iter().position(|row| row.len() != columns) {
+                    return Err(GridError::RaggedRow { line: i + 1, expected: columns, found: matrix[i].len() });
+                }
+                if columns == 0 {
+                    return Ok(Vec::new());
+                }
+                let directions = torus_directions(matrix.len(), columns);
+                Ok(self.find_on_lines(matrix, &directions, true, |direction| loops(matrix, direction)))
+            }
+        }
+    }
+
+    fn find_on_lines(
+        &self,
+        matrix: &Matrix,
+        directions: &[Direction],
+        cyclic: bool,
+        lines: impl Fn(Direction) -> Vec<Vec<(usize, usize)>>,
+    ) -> Vec<WordMatch<'_>> {
+        let mut matches = Vec::new();
+        let mut letters = Vec::new();
+        for &direction in directions {
+            for line in lines(direction) {
+                letters.clear();
+                letters.extend(line.iter().map(|&(i, j)| matrix[i][j]));
+                self.scan_line(&letters, cyclic, |k, word| {
+                    matches.push(WordMatch { word, start: line[k], direction });
+                });
+            }
+        }
+        matches
+    }
+
+    pub fn count(&self, matrix: &Matrix) -> usize {
+        self.find_all(matrix).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Matrix {
+        rows.iter().map(|row| row.chars().collect()).collect()
+    }
+
+    #[test]
+    fn finds_words_in_every_direction() {
+        let matrix = grid(&[
+            "CATX",
+            "AOTA",
+            "TGOC",
+        ]);
+        let search = WordSearch::new(["CAT", "DOG", "TO", "", "CAT"]);
+        let mut found: Vec<(&str, (usize, usize), Direction)> =
+            search.find_all(&matrix).iter().map(|m| (m.word, m.start, m.direction)).collect();
+        found.sort();
+        assert_eq!(found, vec![
+            ("CAT", (0, 0), (0, 1)),
+            ("CAT", (0, 0), (1, 0)),
+            ("TO", (0, 2), (1, -1)),
+            ("TO", (1, 2), (0, -1)),
+            ("TO", (1, 2), (1, 0)),
+            ("TO", (2, 0), (-1, 1)),
+        ]);
+        assert_eq!(search.words(), ["CAT", "DOG", "TO"]);
+        let up = WordMatch { word: "CAT", start: (2, 3), direction: (-1, -1) };
+        assert_eq!(up.cells(), vec![(2, 3), (1, 2), (0, 1)]);
+    }
+
+    #[test]
+    fn handles_non_ascii_letters() {
+        let matrix = grid(&["ÉTÉ", "TxT", "ÉTÉ"]);
+        let search = WordSearch::new(["ÉTÉ"]);
+        // The outer rows and columns, each read both ways.
+        assert_eq!(search.count(&matrix), 8);
+    }
+
+    #[test]
+    fn wraps_around_a_torus() {
+        let matrix = grid(&[
+            "AQXY",
+            "QTQQ",
+            "QQQC",
+        ]);
+        let search = WordSearch::new(["CAT", "QQQQQ"]);
+        assert!(search.find_all(&matrix).is_empty());
+        let found = search.find_all_on(&matrix, Topology::Torus).unwrap();
+        assert_eq!(found, vec![WordMatch { word: "CAT", start: (2, 3), direction: (1, 1) }]);
+        assert_eq!(found[0].cells_on_torus(3, 4), vec![(2, 3), (0, 0), (1, 1)]);
+        // A word longer than its loop would have to reuse a cell.
+        assert!(search.find_all_on(&grid(&["QQQQ"]), Topology::Torus).unwrap().is_empty());
+    }
+
+    #[test]
+    fn small_and_ragged_tori() {
+        let search = WordSearch::new(["AB"]);
+        let mut found: Vec<((usize, usize), Direction)> = search.find_all_on(&grid(&["AB", "BA"]), Topology::Torus)
+            .unwrap().iter().map(|m| (m.start, m.direction)).collect();
+        found.sort();
+        // Each row and column loop is read once, not once per way round.
+        assert_eq!(found, vec![((0, 0), (0, 1)), ((0, 0), (1, 0)), ((1, 1), (0, 1)), ((1, 1), (1, 0))]);
+        assert_eq!(search.find_all_on(&grid(&["AB"]), Topology::Torus).unwrap().len(), 1);
+        assert_eq!(torus_directions(1, 1), vec![(0, 1)]);
+        assert_eq!(torus_directions(3, 3), DIRECTIONS);
+        assert!(search.find_all_on(&Matrix::new(), Topology::Torus).unwrap().is_empty());
+
+        let error = search.find_all_on(&grid(&["ABC", "B"]), Topology::Torus).unwrap_err();
+        assert!(matches!(error, GridError::RaggedRow { line: 2, expected: 3, found: 1 }));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::Matrix;
+
+/// A small rectangular pattern of letters, where `None` matches any letter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Stencil {
+    cells: Vec<Vec<Option<char>>>,
+}
+
+/// How a stencil was turned before matching: reflected left to right first,
+/// if at all, then rotated clockwise by `quarter_turns`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    pub reflected: bool,
+    pub quarter_turns: u8,
+}
+
+/// Which orientations of a stencil to try.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    /// All eight, so a symmetric stencil may match one spot several times.
+    All,
+    /// Orientations that look the same as an earlier one are skipped.
+    Distinct,
+}
+
+/// A place where some orientation of a stencil matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StencilMatch {
+    /// Row and column of the grid cell under the oriented stencil's top-left cell.
+    pub top_left: (usize, usize),
+    pub orientation: Orientation,
+}
+
+impl Stencil {
+    /// Builds a stencil from rows of text in which `wildcard` stands for any
+    /// letter, e.g. `["M.S", ".A.", "M.S"]` with `'.'`.
+    ///
+    /// Panics if there are no rows, a row is empty or rows differ in length.
+    pub fn new(rows: &[&str], wildcard: char) -> Self {
+        let cells: Vec<Vec<Option<char>>> = rows.iter()
+            .map(|row| row.chars().map(|c| (c != wildcard).then_some(c)).collect())
+            .collect();
+        assert!(!cells.is_empty() && !cells[0].is_empty(), "stencil must not be empty");
+        assert!(cells.iter().all(|row| row.len() == cells[0].len()), "stencil rows must have the same length");
+        Stencil { cells }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Option<char> {
+        self.cells[i][j]
+    }
+
+    /// The stencil turned a quarter clockwise.
+    pub fn rotated(&self) -> Self {
+        let rows = self.rows();
+        let cells = (0..self.columns())
+            .map(|i| (0..rows).map(|j| self.cells[rows - 1 - j][i]).collect())
+            .collect();
+        Stencil { cells }
+    }
+
+    /// The stencil mirrored left to right.
+    pub fn reflected(&self) -> Self {
+        let cells = self.cells.iter().map(|row| row.iter().rev().copied().collect()).collect();
+        Stencil { cells }
+    }
+
+    pub fn oriented(&self, orientation: Orientation) -> Self {
+        let mut stencil = if orientation.reflected { self.reflected() } else { self.clone() };
+        for _ in 0..orientation.quarter_turns % 4 {
+            stencil = stencil.rotated();
+        }
+        stencil
+    }
+
+    pub fn orientations(&self, symmetry: Symmetry) -> Vec<(Orientation, Stencil)> {
+        let mut orientations: Vec<(Orientation, Stencil)> = Vec::with_capacity(8);
+        for reflected in [false, true] {
+            for quarter_turns in 0..4 {
+                let orientation = Orientation { reflected, quarter_turns };
+                let stencil = self.oriented(orientation);
+                if symmetry == Symmetry::All || orientations.iter().all(|(_, other)| *other != stencil) {
+                    orientations.push((orientation, stencil));
+                }
+            }
+        }
+        orientations
+    }
+
+    /// Whether the stencil, as it is, matches with its top-left cell on `(i, j)`.
+    pub fn matches_at(&self, matrix: &Matrix, i: usize, j: usize) -> bool {
+        self.cells.iter().enumerate().all(|(di, row)| {
+            row.iter().enumerate().all(|(dj, expected)| {
+                let found = matrix.get(i + di).and_then(|row| row.get(j + dj));
+                match (expected, found) {
+                    (_, None) => false,
+                    (None, Some(_)) => true,
+                    (Some(expected), Some(found)) => expected == found,
+                }
+            })
+        })
+    }
+
+    /// Every placement of every orientation that matches the grid.
+    pub fn find_all(&self, matrix: &Matrix, symmetry: Symmetry) -> Vec<StencilMatch> {
+        let mut matches = Vec::new();
+        for (orientation, stencil) in self.orientations(symmetry) {
+            for (i, row) in matrix.iter().enumerate() {
+                for j in 0..row.len() {
+                    if stencil.matches_at(matrix, i, j) {
+                        matches.push(StencilMatch { top_left: (i, j), orientation });
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    /// The grid cells under the letters of the matched stencil; cells under
+    /// wildcards are not part of the match.
+    pub fn cells(&self, found: &StencilMatch) -> Vec<(usize, usize)> {
+        let stencil = self.oriented(found.orientation);
+        let (i, j) = found.top_left;
+        (0..stencil.rows())
+            .flat_map(|di| (0..stencil.columns()).map(move |dj| (di, dj)))
+            .filter(|&(di, dj)| stencil.get(di, dj).is_some())
+            .map(|(di, dj)| (i + di, j + dj))
+            .collect()
+    }
+
+    pub fn count(&self, matrix: &Matrix, symmetry: Symmetry) -> usize {
+        self.find_all(matrix, symmetry).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Matrix {
+        rows.iter().map(|row| row.chars().collect()).collect()
+    }
+
+    #[test]
+    fn orientations() {
+        let stencil = Stencil::new(&["AB", "C."], '.');
+        assert_eq!(stencil.rotated(), Stencil::new(&["CA", ".B"], '.'));
+        assert_eq!(stencil.reflected(), Stencil::new(&["BA", ".C"], '.'));
+        assert_eq!(stencil.rotated().rotated().rotated().rotated(), stencil);
+        assert_eq!(stencil.orientations(Symmetry::Distinct).len(), 8);
+
+        let tall = Stencil::new(&["AB", "CD", "EF"], '.');
+        assert_eq!((tall.rotated().rows(), tall.rotated().columns()), (2, 3));
+
+        let x = Stencil::new(&["M.S", ".A.", "M.S"], '.');
+        assert_eq!(x.orientations(Symmetry::All).len(), 8);
+        assert_eq!(x.orientations(Symmetry::Distinct).len(), 4);
+        assert_eq!(Stencil::new(&["A"], '.').orientations(Symmetry::Distinct).len(), 1);
+    }
+
+    #[test]
+    fn matches_under_rotation() {
+        let matrix = grid(&[
+            "SxSx",
+            "xAxx",
+            "MxMx",
+        ]);
+        let x = Stencil::new(&["M.S", ".A.", "M.S"], '.');
+        let found = x.find_all(&matrix, Symmetry::Distinct);
+        assert_eq!(found, vec![StencilMatch {
+            top_left: (0, 0),
+            orientation: Orientation { reflected: false, quarter_turns: 3 },
+        }]);
+        assert_eq!(x.cells(&found[0]), vec![(0, 0), (0, 2), (1, 1), (2, 0), (2, 2)]);
+        assert_eq!(x.count(&matrix, Symmetry::All), 2);
+        assert_eq!(x.count(&grid(&["MS", "AM"]), Symmetry::All), 0);
+    }
+}